@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+use crate::error::MyContractError;
+
+// Layout of the Ed25519 program's instruction data: a signature count and
+// padding byte, followed by one offsets record per signature
+const OFFSETS_START: usize = 2;
+const OFFSETS_LEN: usize = 14;
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+pub const ATTESTATION_LEN: usize = 72;
+
+/// Message the verifier signs to let `buyer` purchase from `ico_state` until
+/// `expires_at`.
+pub fn message(ico_state: &Pubkey, buyer: &Pubkey, expires_at: i64) -> [u8; ATTESTATION_LEN] {
+    let mut message = [0u8; ATTESTATION_LEN];
+    message[..32].copy_from_slice(ico_state.as_ref());
+    message[32..64].copy_from_slice(buyer.as_ref());
+    message[64..].copy_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+/// Requires the instruction right before the current one to be an Ed25519
+/// program check of an unexpired attestation for `buyer`, signed by `verifier`.
+/// The Ed25519 program has already failed the transaction if the signature is bad.
+pub fn verify(
+    instructions: &AccountInfo,
+    verifier: &Pubkey,
+    ico_state: &Pubkey,
+    buyer: &Pubkey,
+    now: i64,
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    require!(current > 0, MyContractError::MissingVerifierSignature);
+    let ix = load_instruction_at_checked(current as usize - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        MyContractError::MissingVerifierSignature
+    );
+
+    let (signer, signed) =
+        parse_ed25519(&ix.data).ok_or(MyContractError::MissingVerifierSignature)?;
+    require!(
+        signer == verifier.as_ref()
+            && signed.len() == ATTESTATION_LEN
+            && signed[..32] == ico_state.to_bytes()
+            && signed[32..64] == buyer.to_bytes(),
+        MyContractError::InvalidVerifierSignature
+    );
+    let expires_at = i64::from_le_bytes(signed[64..].try_into().unwrap());
+    require!(now <= expires_at, MyContractError::AttestationExpired);
+    Ok(())
+}
+
+/// Public key and message of an Ed25519 program instruction checking exactly one
+/// signature whose data is all inline.
+pub fn parse_ed25519(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.len() < OFFSETS_START + OFFSETS_LEN || data[0] != 1 {
+        return None;
+    }
+    let field = |index: usize| {
+        let at = OFFSETS_START + index * 2;
+        u16::from_le_bytes([data[at], data[at + 1]])
+    };
+    // Offsets record: signature, its instruction, public key, its instruction,
+    // message, message size, message instruction
+    if field(1) != THIS_INSTRUCTION || field(3) != THIS_INSTRUCTION || field(6) != THIS_INSTRUCTION
+    {
+        return None;
+    }
+
+    let public_key = field(2) as usize;
+    let message = field(4) as usize;
+    let message_len = field(5) as usize;
+    Some((
+        data.get(public_key..public_key + 32)?,
+        data.get(message..message + message_len)?,
+    ))
+}
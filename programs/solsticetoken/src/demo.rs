@@ -1,75 +1,61 @@
-use anchor_lang::prelude::*;
-use anchor_lang::solana_program::pubkey::Pubkey;
-use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
-
-declare_id!("Fg6PaFhzQxQfen8DHuZQDVpokndbCWTJcDJTYYkg4LTG");
-
-#[program]
-pub mod demo_ico {
-    use super::*;
-
-    pub fn start_ico(ctx: Context<StartIco>, phase_details: Vec<Phase>) -> Result<()> {
-        let ico_state = &mut ctx.accounts.ico_state;
-        ico_state.phases = phase_details;
-        Ok(())
-    }
-
-    pub fn buy_tokens(ctx: Context<BuyTokens>, amount_sol: u64) -> Result<()> {
-        let ico_state = &ctx.accounts.ico_state;
-
-        if let Some(phase) = ico_state.current_phase() {
-            let tokens_to_mint = amount_sol / phase.token_price; // Simplified calculation
-                                                                 // Here you would include logic to mint tokens to the vesting contract
-            msg!("Tokens to mint: {}", tokens_to_mint);
-        } else {
-            return Err(ErrorCode::OutsideICOPhase.into());
-        }
-
-        Ok(())
-    }
-}
-
-#[derive(Accounts)]
-pub struct StartIco<'info> {
-    #[account(init, payer = admin, space = 9000)]
-    pub ico_state: Account<'info, ICOState>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+//! Preset reproducing the old standalone `demo_ico` program on top of the main
+//! sale engine: back-to-back phases with fixed start/end timestamps and a flat
+//! lamport price per whole token.
 
-#[derive(Accounts)]
-pub struct BuyTokens<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-    #[account(mut)]
-    pub ico_state: Account<'info, ICOState>,
-    pub system_program: Program<'info, System>,
-}
-
-#[account]
-pub struct ICOState {
-    pub phases: Vec<Phase>,
-}
+use anchor_lang::prelude::*;
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct Phase {
-    pub token_price: u64,
-    pub start: i64,
-    pub end: i64,
+use crate::error::MyContractError;
+use crate::state::{
+    BotProtection, PhaseDetail, PhaseTiming, PriceSchedule, SaleConfig, TokenPrice,
+};
+
+pub const DEMO_PHASE_COUNT: u8 = 3;
+pub const DEMO_PHASE_LENGTH: i64 = 24 * 60 * 60; // One day per phase
+pub const DEMO_PHASE_ALLOCATION: u64 = 1_000_000 * TOKEN_UNIT; // 1M Pledge per phase
+
+const TOKEN_UNIT: u64 = 1_000_000_000; // Pledge has 9 decimals
+
+/// Demo schedule opening at `start`. Phase `n` costs `(n + 1) * lamports_per_token`
+/// lamports per whole token.
+pub fn phases(start: i64, lamports_per_token: u64) -> Result<Vec<PhaseDetail>> {
+    (0..DEMO_PHASE_COUNT)
+        .map(|phase| {
+            let phase_start = DEMO_PHASE_LENGTH
+                .checked_mul(phase as i64)
+                .and_then(|offset| start.checked_add(offset))
+                .ok_or(MyContractError::MathOverflow)?;
+            Ok(PhaseDetail {
+                phase,
+                timing: PhaseTiming::Absolute {
+                    start: phase_start,
+                    end: phase_start
+                        .checked_add(DEMO_PHASE_LENGTH)
+                        .ok_or(MyContractError::MathOverflow)?,
+                },
+                token_price: TokenPrice {
+                    tokens: TOKEN_UNIT,
+                    lamports: lamports_per_token
+                        .checked_mul(phase as u64 + 1)
+                        .ok_or(MyContractError::MathOverflow)?,
+                },
+                token_allocation: DEMO_PHASE_ALLOCATION,
+                tokens_sold: 0,
+                purchases: 0,
+                max_per_wallet: 0,
+                allowlist_root: None,
+                bot_protection: BotProtection::default(),
+                price_schedule: PriceSchedule::Fixed,
+            })
+        })
+        .collect()
 }
 
-impl ICOState {
-    fn current_phase(&self) -> Option<&Phase> {
-        let now = Clock::get().unwrap().unix_timestamp;
-        self.phases
-            .iter()
-            .find(|phase| now >= phase.start && now <= phase.end)
-    }
+/// Total to pass as `total_tokens_allocated` alongside `phases`.
+pub fn total_tokens_allocated() -> u64 {
+    DEMO_PHASE_ALLOCATION * DEMO_PHASE_COUNT as u64
 }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("The transaction is outside the ICO phase.")]
-    OutsideICOPhase,
+/// The demo sells to anyone, with no limits or soft cap.
+pub fn config() -> SaleConfig {
+    SaleConfig::default()
 }
@@ -4,4 +4,114 @@ use anchor_lang::prelude::*;
 pub enum MyContractError {
     #[msg("The purchase would exceed the total tokens allocated for sale.")]
     OverPurchase,
+    #[msg("The supply allocation must be non-zero and add up to 10000 basis points.")]
+    InvalidSupplyAllocation,
+    #[msg("Only the ICO admin can perform this action.")]
+    Unauthorized,
+    #[msg("The ICO has already started.")]
+    IcoAlreadyStarted,
+    #[msg("The sale is paused.")]
+    SalePaused,
+    #[msg("Reward claims are paused.")]
+    ClaimsPaused,
+    #[msg("Treasury payouts are paused.")]
+    TreasuryPaused,
+    #[msg("The token price must have a non-zero token and lamport amount.")]
+    InvalidPrice,
+    #[msg("The payment is too small to buy a single base unit.")]
+    PurchaseTooSmall,
+    #[msg("Arithmetic overflow.")]
+    MathOverflow,
+    #[msg("Arithmetic underflow.")]
+    MathUnderflow,
+    #[msg("The phase schedule must contain at least one phase.")]
+    EmptyPhaseSchedule,
+    #[msg("The phase schedule has more phases than allowed.")]
+    TooManyPhases,
+    #[msg("The sale must start in the future.")]
+    StartNotInFuture,
+    #[msg("Phases must be numbered sequentially from zero.")]
+    InvalidPhaseOrder,
+    #[msg("Phase durations must be positive.")]
+    InvalidPhaseDuration,
+    #[msg("Phase windows must be non-empty and must not overlap earlier phases.")]
+    InvalidPhaseWindow,
+    #[msg("A phase is cheaper than the phase before it.")]
+    PhasePriceDecreased,
+    #[msg("The sale is not active.")]
+    SaleNotActive,
+    #[msg("The sale has not ended yet.")]
+    SaleNotEnded,
+    #[msg("The sale has already been finalized or cancelled.")]
+    SaleClosed,
+    #[msg("Each phase needs a non-zero allocation and together they can't exceed the sale allocation.")]
+    InvalidPhaseAllocation,
+    #[msg("The purchase would exceed the current phase's allocation.")]
+    PhaseCapExceeded,
+    #[msg("The purchase is below the minimum purchase amount.")]
+    BelowMinimumPurchase,
+    #[msg("The purchase would exceed the per-wallet limit.")]
+    WalletLimitExceeded,
+    #[msg("The purchase would exceed the per-wallet limit for this phase.")]
+    PhaseWalletLimitExceeded,
+    #[msg("The buyer is not on this phase's allowlist.")]
+    NotAllowlisted,
+    #[msg("The purchase would exceed the buyer's allowlist allocation.")]
+    AllowlistCapExceeded,
+    #[msg("The sale does not accept this payment mint.")]
+    PaymentMintNotAccepted,
+    #[msg("The payment mint is already accepted.")]
+    PaymentMintAlreadyAccepted,
+    #[msg("The sale accepts too many payment mints.")]
+    TooManyPaymentMints,
+    #[msg("The price feed does not match the sale's oracle.")]
+    OracleFeedMismatch,
+    #[msg("The price feed account is not a valid Pyth price account.")]
+    InvalidOracleAccount,
+    #[msg("The oracle has no usable price.")]
+    OraclePriceUnavailable,
+    #[msg("The oracle price is stale.")]
+    OraclePriceStale,
+    #[msg("The oracle price confidence interval is too wide.")]
+    OracleConfidenceTooWide,
+    #[msg("The sale has not reached its soft cap.")]
+    SoftCapNotReached,
+    #[msg("Proceeds can only be withdrawn once the sale is finalized or reaches its soft cap.")]
+    SaleNotFinalized,
+    #[msg("Refunds are only available for cancelled sales or sales that missed the soft cap.")]
+    RefundsNotOpen,
+    #[msg("There is nothing left to refund.")]
+    NothingToRefund,
+    #[msg("Proceeds have already been withdrawn from this sale.")]
+    ProceedsWithdrawn,
+    #[msg("This wallet must wait before purchasing again.")]
+    PurchaseCooldown,
+    #[msg("Too many purchases in this slot, try again.")]
+    SlotPurchaseLimitReached,
+    #[msg("The purchase must be co-signed by the sale's verifier.")]
+    MissingVerifierSignature,
+    #[msg("The verifier co-signature does not cover this purchase.")]
+    InvalidVerifierSignature,
+    #[msg("The verifier attestation has expired.")]
+    AttestationExpired,
+    #[msg("buy_tokens only buys for the signer, use buy_tokens_for.")]
+    BeneficiaryNotBuyer,
+    #[msg("Batch auctions only accept SOL.")]
+    BatchAuctionSolOnly,
+    #[msg("The sale is not a batch auction.")]
+    NotBatchAuction,
+    #[msg("This purchase has already been settled.")]
+    AlreadySettled,
+    #[msg("The withdrawal exceeds the proceeds that cleared the auction.")]
+    WithdrawalExceedsProceeds,
+    #[msg("The sale vault must keep its rent-exempt reserve.")]
+    SaleVaultReserve,
+    #[msg("The payment does not cover the rounded token amount.")]
+    InsufficientPayment,
+    #[msg("The oracle's maximum price age must be positive.")]
+    InvalidOracleConfig,
+    #[msg("The soft cap exceeds the tokens allocated for sale.")]
+    InvalidSoftCap,
+    #[msg("The beneficiary must sign to burn the tokens this payment bought.")]
+    BeneficiaryMustBurn,
 }
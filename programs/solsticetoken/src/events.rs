@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::state::TokenPrice;
+
+#[event]
+pub struct PauseStateChanged {
+    pub ico_state: Pubkey,
+    pub pause_flags: u8,
+    pub reason: u16,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct PhaseChanged {
+    pub ico_state: Pubkey,
+    pub phase: u8,
+    pub token_price: TokenPrice,
+    pub phase_started_at: i64,
+}
+
+#[event]
+pub struct ProceedsWithdrawn {
+    pub ico_state: Pubkey,
+    pub payment_mint: Option<Pubkey>, // None for SOL
+    pub amount: u64,
+    pub destination: Pubkey,
+}
@@ -1,11 +1,31 @@
-mod error;
-mod state;
+pub mod attestation;
+#[cfg(feature = "demo")]
+pub mod demo;
+pub mod error;
+pub mod events;
+pub mod merkle;
+pub mod oracle;
+pub mod pricing;
+pub mod rewards;
+pub mod state;
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
-use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::token::spl_token::instruction::AuthorityType;
+use anchor_spl::token::{self, Burn, Mint, MintTo, SetAuthority, Token, TokenAccount, Transfer};
 use error::MyContractError;
-use state::{ICOState, PhaseDetail, UserState};
+use events::{PauseStateChanged, PhaseChanged, ProceedsWithdrawn};
+use merkle::AllowlistProof;
+use pricing::Quote;
+use state::{
+    ICOState, PaymentMint, PaymentPricing, PaymentRecord, PhaseDetail, SaleConfig, SaleStatus,
+    SupplyAllocation, UserState, DISTRIBUTION_SEED, ICO_SEED, LIQUIDITY_SEED, MAX_PAYMENT_MINTS,
+    MAX_PHASES, MINT_AUTHORITY_SEED, PAUSE_ALL, PAUSE_CLAIMS, PAUSE_SALES, PAUSE_TREASURY,
+    PAYMENT_SEED, PAYMENT_VAULT_SEED, REWARDS_VAULT_SEED, SALE_VAULT_SEED, SUPPLY_AUTHORITY_SEED,
+    TEAM_SEED, USER_SEED,
+};
 
 declare_id!("HmKiLcNDNciqozcGuZshomJ72HmkuGmDoVGoLChPJFag");
 
@@ -19,22 +39,276 @@ pub mod solsticetoken {
         start_timestamp: i64,
         total_tokens_allocated: u64,
         phase_details: Vec<PhaseDetail>,
+        config: SaleConfig,
     ) -> Result<()> {
         let ico_state = &mut ctx.accounts.ico_state;
+        ico_state.pledge_mint = ctx.accounts.pledge_token_mint.key();
+        ico_state.bump = ctx.bumps.ico_state;
+        ico_state.mint_authority_bump = ctx.bumps.mint_authority;
+        ico_state.admin = ctx.accounts.admin.key();
+        ico_state.treasury = ctx.accounts.admin.key();
+        ico_state.emergency_authority = ctx.accounts.admin.key();
         ico_state.start_timestamp = start_timestamp;
         ico_state.total_tokens_allocated = total_tokens_allocated;
-        ico_state.phase_details = phase_details;
+        ico_state.set_phase_details(phase_details);
+        ico_state.config = config;
+        ico_state.validate_schedule(Clock::get()?.unix_timestamp)?;
+
+        // Keep the vault rent exempt so small purchases and the last refunds
+        // never leave it below the minimum balance
+        let reserve = Rent::get()?
+            .minimum_balance(0)
+            .saturating_sub(ctx.accounts.sale_vault.lamports());
+        if reserve > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.admin.to_account_info(),
+                to: ctx.accounts.sale_vault.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            system_program::transfer(CpiContext::new(cpi_program, cpi_accounts), reserve)?;
+        }
+        Ok(())
+    }
+
+    pub fn update_phases(
+        ctx: Context<AdminOnly>,
+        start_timestamp: i64,
+        phase_details: Vec<PhaseDetail>,
+    ) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now < ico_state.start_timestamp,
+            MyContractError::IcoAlreadyStarted
+        );
+        ico_state.start_timestamp = start_timestamp;
+        ico_state.set_phase_details(phase_details);
+        ico_state.validate_schedule(now)
+    }
+
+    pub fn crank_phase(ctx: Context<CrankPhase>) -> Result<()> {
+        sync_status(&mut ctx.accounts.ico_state, Clock::get()?.unix_timestamp)?;
+        Ok(())
+    }
+
+    pub fn finalize_sale(ctx: Context<AdminOnly>) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        require!(
+            ico_state.compute_status(Clock::get()?.unix_timestamp)? == SaleStatus::Ended,
+            MyContractError::SaleNotEnded
+        );
+        require!(
+            ico_state.soft_cap_reached(),
+            MyContractError::SoftCapNotReached
+        );
+        ico_state.status = SaleStatus::Finalized;
+        Ok(())
+    }
+
+    pub fn cancel_sale(ctx: Context<AdminOnly>) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        require!(
+            !matches!(
+                ico_state.status,
+                SaleStatus::Finalized | SaleStatus::Cancelled
+            ),
+            MyContractError::SaleClosed
+        );
+        // Refunds would come out of a vault that has already been drained
+        require!(
+            !ico_state.proceeds_withdrawn,
+            MyContractError::ProceedsWithdrawn
+        );
+        ico_state.status = SaleStatus::Cancelled;
+        Ok(())
+    }
+
+    pub fn pause(ctx: Context<EmergencyStop>, flags: u8, reason: u16) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        ico_state.pause_flags |= flags & PAUSE_ALL;
+        emit!(PauseStateChanged {
+            ico_state: ico_state.key(),
+            pause_flags: ico_state.pause_flags,
+            reason,
+            authority: ctx.accounts.authority.key(),
+        });
+        Ok(())
+    }
+
+    pub fn resume(ctx: Context<EmergencyStop>, flags: u8, reason: u16) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        ico_state.pause_flags &= !flags;
+        emit!(PauseStateChanged {
+            ico_state: ico_state.key(),
+            pause_flags: ico_state.pause_flags,
+            reason,
+            authority: ctx.accounts.authority.key(),
+        });
+        Ok(())
+    }
+
+    pub fn set_emergency_authority(
+        ctx: Context<AdminOnly>,
+        emergency_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.ico_state.emergency_authority = emergency_authority;
+        Ok(())
+    }
+
+    pub fn set_treasury(ctx: Context<AdminOnly>, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.ico_state.treasury = treasury;
+        Ok(())
+    }
+
+    pub fn propose_admin(ctx: Context<AdminOnly>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.ico_state.pending_admin = Some(new_admin);
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        ico_state.admin = ctx.accounts.new_admin.key();
+        ico_state.pending_admin = None;
+        Ok(())
+    }
+
+    pub fn initialize_solhit_token(
+        ctx: Context<InitializeSolhitToken>,
+        allocation: SupplyAllocation,
+    ) -> Result<()> {
+        InitializeSolhitToken::execute(ctx, allocation)
+    }
+
+    pub fn buy_tokens(
+        ctx: Context<BuyTokens>,
+        amount_sol: u64,
+        allowlist: Option<AllowlistProof>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.beneficiary.key(),
+            ctx.accounts.buyer.key(),
+            MyContractError::BeneficiaryNotBuyer
+        );
+        BuyTokens::buy_tokens(ctx, amount_sol, allowlist)
+    }
+
+    /// Like `buy_tokens`, but the Pledge tokens and the purchase record go to
+    /// `beneficiary` while the signer only pays. What the signer paid is kept in
+    /// its own `PaymentRecord`, so any refund goes back to the signer.
+    pub fn buy_tokens_for(
+        ctx: Context<BuyTokens>,
+        amount_sol: u64,
+        allowlist: Option<AllowlistProof>,
+    ) -> Result<()> {
+        BuyTokens::buy_tokens(ctx, amount_sol, allowlist)
+    }
+
+    pub fn buy_tokens_with_token(
+        ctx: Context<BuyTokensWithToken>,
+        amount: u64,
+        allowlist: Option<AllowlistProof>,
+    ) -> Result<()> {
+        BuyTokensWithToken::buy_tokens_with_token(ctx, amount, allowlist)
+    }
+
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        ClaimRefund::claim_refund(ctx)
+    }
+
+    pub fn withdraw_proceeds(ctx: Context<WithdrawProceeds>, amount: u64) -> Result<()> {
+        WithdrawProceeds::withdraw_proceeds(ctx, amount)
+    }
+
+    pub fn settle_batch(ctx: Context<SettleBatch>) -> Result<()> {
+        SettleBatch::settle_batch(ctx)
+    }
+
+    pub fn add_payment_mint(ctx: Context<AddPaymentMint>, pricing: PaymentPricing) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        let mint = ctx.accounts.payment_mint.key();
+        require!(
+            ico_state.payment_pricing(&mint).is_err(),
+            MyContractError::PaymentMintAlreadyAccepted
+        );
+        require!(
+            ico_state.payment_mints.len() < MAX_PAYMENT_MINTS,
+            MyContractError::TooManyPaymentMints
+        );
+        pricing.validate(&ico_state.phase_details, &ico_state.config)?;
+        ico_state.payment_mints.push(PaymentMint { mint, pricing });
         Ok(())
     }
 }
 
 #[derive(Accounts)]
 pub struct StartIco<'info> {
-    #[account(init, payer = admin, space = 10240)]
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ICOState::INIT_SPACE,
+        seeds = [ICO_SEED, pledge_token_mint.key().as_ref()],
+        bump
+    )]
     pub ico_state: Account<'info, ICOState>,
+    /// CHECK: PDA that signs every Pledge mint, never holds data
+    #[account(seeds = [MINT_AUTHORITY_SEED, ico_state.key().as_ref()], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    // Created here so nobody can start a sale for a mint someone else set up
+    #[account(init, payer = admin, mint::decimals = 9, mint::authority = mint_authority)]
+    pub pledge_token_mint: Account<'info, Mint>, // PledgeToken mint account
+    #[account(mut, seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: SystemAccount<'info>,
     #[account(mut)]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct AdminOnly<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump,
+        has_one = admin @ MyContractError::Unauthorized
+    )]
+    pub ico_state: Account<'info, ICOState>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CrankPhase<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump
+    )]
+    pub ico_state: Account<'info, ICOState>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyStop<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump,
+        constraint = authority.key() == ico_state.emergency_authority
+            || authority.key() == ico_state.admin @ MyContractError::Unauthorized
+    )]
+    pub ico_state: Account<'info, ICOState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump,
+        constraint = ico_state.pending_admin == Some(new_admin.key()) @ MyContractError::Unauthorized
+    )]
+    pub ico_state: Account<'info, ICOState>,
+    pub new_admin: Signer<'info>,
 }
 
 #[derive(Accounts)]
@@ -42,11 +316,46 @@ pub struct InitializeSolhitToken<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
     #[account(init, payer = admin, mint::decimals = 9, mint::authority = admin)]
-    pub solhit_token_mint: Account<'info, Mint>, // SolhitToken mint account
-    #[account(init, payer = admin, token::mint = solhit_token_mint, token::authority = vesting_contract)]
-    pub vesting_contract_account: Account<'info, TokenAccount>, // Account to hold 4 million tokens for rewards
-    #[account(init, payer = admin, token::mint = solhit_token_mint, token::authority = admin)]
-    pub distribution_account: Account<'info, TokenAccount>, // For the 10 million tokens distribution
+    pub solhit_token_mint: Box<Account<'info, Mint>>, // SolhitToken mint account
+    /// CHECK: PDA that owns every genesis bucket, never holds data
+    #[account(seeds = [SUPPLY_AUTHORITY_SEED, solhit_token_mint.key().as_ref()], bump)]
+    pub supply_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [REWARDS_VAULT_SEED, solhit_token_mint.key().as_ref()],
+        bump,
+        token::mint = solhit_token_mint,
+        token::authority = supply_authority
+    )]
+    pub rewards_vault: Box<Account<'info, TokenAccount>>, // Rewards paid out by vesting
+    #[account(
+        init,
+        payer = admin,
+        seeds = [DISTRIBUTION_SEED, solhit_token_mint.key().as_ref()],
+        bump,
+        token::mint = solhit_token_mint,
+        token::authority = supply_authority
+    )]
+    pub distribution_account: Box<Account<'info, TokenAccount>>, // Public distribution
+    #[account(
+        init,
+        payer = admin,
+        seeds = [TEAM_SEED, solhit_token_mint.key().as_ref()],
+        bump,
+        token::mint = solhit_token_mint,
+        token::authority = supply_authority
+    )]
+    pub team_account: Box<Account<'info, TokenAccount>>, // Team allocation
+    #[account(
+        init,
+        payer = admin,
+        seeds = [LIQUIDITY_SEED, solhit_token_mint.key().as_ref()],
+        bump,
+        token::mint = solhit_token_mint,
+        token::authority = supply_authority
+    )]
+    pub liquidity_account: Box<Account<'info, TokenAccount>>, // Exchange liquidity
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
     pub token_program: Program<'info, Token>,
@@ -55,40 +364,45 @@ pub struct InitializeSolhitToken<'info> {
 impl<'info> InitializeSolhitToken<'info> {
     pub fn execute(
         ctx: Context<InitializeSolhitToken>,
-        exchange_or_other_destination: Pubkey,
+        allocation: SupplyAllocation,
     ) -> Result<()> {
-        // Define the amounts for minting
-        let rewards_amount: u64 =
-            4_000_000 * 10u64.pow(ctx.accounts.solhit_token_mint.decimals as u32); // 4 million tokens
-        let distribution_amount: u64 =
-            10_000_000 * 10u64.pow(ctx.accounts.solhit_token_mint.decimals as u32); // 10 million tokens
-
-        // Mint 4 million Solhit Tokens to the vesting_contract_account (this contract's account for managing rewards)
-        token::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.solhit_token_mint.to_account_info(),
-                    to: ctx.accounts.vesting_contract_account.to_account_info(),
-                    authority: ctx.accounts.admin.to_account_info(),
-                },
-                &[&[/* seeds used for the vesting_contract_account */]],
-            ),
-            rewards_amount,
-        )?;
+        let buckets = allocation.split()?;
+
+        // Mint every bucket while the admin still holds the mint authority
+        let destinations = [
+            ctx.accounts.rewards_vault.to_account_info(),
+            ctx.accounts.distribution_account.to_account_info(),
+            ctx.accounts.team_account.to_account_info(),
+            ctx.accounts.liquidity_account.to_account_info(),
+        ];
+        for (destination, amount) in destinations.into_iter().zip(buckets) {
+            if amount == 0 {
+                continue;
+            }
+            token::mint_to(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: ctx.accounts.solhit_token_mint.to_account_info(),
+                        to: destination,
+                        authority: ctx.accounts.admin.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+        }
 
-        // Mint the remaining 10 million Solhit Tokens to the specified distribution account
-        // Ensure a TokenAccount exists for the exchange_or_other_destination and is passed here
-        token::mint_to(
+        // Revoke the mint authority so the total supply is fixed forever
+        token::set_authority(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.solhit_token_mint.to_account_info(),
-                    to: ctx.accounts.distribution_account.to_account_info(), // This should be the TokenAccount for exchange_or_other_destination
-                    authority: ctx.accounts.admin.to_account_info(),
+                SetAuthority {
+                    account_or_mint: ctx.accounts.solhit_token_mint.to_account_info(),
+                    current_authority: ctx.accounts.admin.to_account_info(),
                 },
             ),
-            distribution_amount,
+            AuthorityType::MintTokens,
+            None,
         )?;
 
         Ok(())
@@ -98,68 +412,790 @@ impl<'info> InitializeSolhitToken<'info> {
 #[derive(Accounts)]
 pub struct BuyTokens<'info> {
     #[account(mut)]
-    pub buyer: Signer<'info>,
-    #[account(mut)]
-    pub buyer_token_account: Account<'info, TokenAccount>, // Buyer's PledgeToken account
-    #[account(mut)]
+    pub buyer: Signer<'info>, // Pays for the purchase
+    /// CHECK: Any wallet; the buyer itself unless buying on someone's behalf
+    pub beneficiary: UncheckedAccount<'info>,
+    #[account(mut, token::mint = pledge_token_mint, token::authority = beneficiary)]
+    pub buyer_token_account: Account<'info, TokenAccount>, // Beneficiary's PledgeToken account
+    #[account(mut, seeds = [ICO_SEED, pledge_token_mint.key().as_ref()], bump = ico_state.bump)]
     pub ico_state: Account<'info, ICOState>, // ICO state account
-    #[account(mut)]
-    pub user_state: Account<'info, UserState>, // User state for this buyer
-    #[account(mut)]
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + UserState::INIT_SPACE,
+        seeds = [USER_SEED, ico_state.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>, // User state for the beneficiary
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + PaymentRecord::INIT_SPACE,
+        seeds = [
+            PAYMENT_SEED,
+            ico_state.key().as_ref(),
+            beneficiary.key().as_ref(),
+            buyer.key().as_ref()
+        ],
+        bump
+    )]
+    pub payment_record: Box<Account<'info, PaymentRecord>>, // What this buyer paid for the beneficiary
+    #[account(mut, address = ico_state.pledge_mint, mint::authority = mint_authority)]
     pub pledge_token_mint: Account<'info, Mint>, // PledgeToken mint account
+    /// CHECK: PDA that signs the MintTo CPI, never holds data
+    #[account(seeds = [MINT_AUTHORITY_SEED, ico_state.key().as_ref()], bump = ico_state.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(mut, seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: SystemAccount<'info>, // Collects the SOL paid by buyers
+    /// CHECK: Matched against the sale's oracle config and parsed by `oracle::read_price`
+    pub price_feed: Option<UncheckedAccount<'info>>, // SOL/USD feed, only for USD-priced sales
     /// CHECK: This is safe to do because we are only reading the data
     pub clock: Sysvar<'info, Clock>, // For accessing the current blockchain timestamp
+    /// CHECK: Instructions sysvar, read for the verifier's Ed25519 co-signature
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>, // Only for phases with a verifier
     pub token_program: Program<'info, Token>, // SPL Token program
+    pub system_program: Program<'info, System>,
 }
 
 impl<'info> BuyTokens<'info> {
-    pub fn buy_tokens(ctx: Context<BuyTokens>, amount_sol: u64) -> Result<()> {
-        let ico_state = &mut ctx.accounts.ico_state;
+    pub fn buy_tokens(
+        ctx: Context<BuyTokens>,
+        amount_sol: u64,
+        allowlist: Option<AllowlistProof>,
+    ) -> Result<()> {
         let user_state = &mut ctx.accounts.user_state;
+        if user_state.user == Pubkey::default() {
+            user_state.user = ctx.accounts.beneficiary.key();
+            user_state.bump = ctx.bumps.user_state;
+        }
+        let payment_record = &mut ctx.accounts.payment_record;
+        if payment_record.payer == Pubkey::default() {
+            payment_record.beneficiary = ctx.accounts.beneficiary.key();
+            payment_record.payer = ctx.accounts.buyer.key();
+            payment_record.bump = ctx.bumps.payment_record;
+        }
+        // USD-priced sales convert the SOL payment through the oracle first
+        let now = ctx.accounts.clock.unix_timestamp;
+        let pricing = match ctx.accounts.ico_state.config.oracle {
+            Some(oracle_config) => {
+                let feed = ctx
+                    .accounts
+                    .price_feed
+                    .as_ref()
+                    .ok_or(MyContractError::OracleFeedMismatch)?;
+                let rate = oracle::sol_usd_rate(feed, &oracle_config, now)?;
+                Some(PaymentPricing::Converted(rate))
+            }
+            None => None,
+        };
+        let quote = process_purchase(
+            &mut ctx.accounts.ico_state,
+            user_state,
+            &ctx.accounts.clock,
+            amount_sol,
+            pricing.as_ref(),
+            allowlist,
+            ctx.accounts.instructions.as_ref(),
+        )?;
+        let batch_auction = ctx.accounts.ico_state.config.batch_auction;
+        ctx.accounts.payment_record.record_payment(
+            None,
+            quote.cost,
+            quote.tokens,
+            batch_auction,
+        )?;
+
+        // Move the buyer's SOL into the sale vault
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.buyer.to_account_info(),
+            to: ctx.accounts.sale_vault.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        system_program::transfer(cpi_ctx, quote.cost)?;
 
-        // Determine the current phase and token price
-        let current_phase = determine_current_phase(&ico_state, &ctx.accounts.clock);
-        let token_price = ico_state.phase_details[current_phase as usize].token_price;
+        // Batch auction bids receive their tokens at settlement
+        if batch_auction {
+            return Ok(());
+        }
+        mint_pledge(
+            &ctx.accounts.ico_state,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pledge_token_mint,
+            &ctx.accounts.buyer_token_account,
+            &ctx.accounts.mint_authority,
+            quote.tokens,
+        )
+    }
+}
 
-        // Calculate the number of tokens to mint
-        let tokens_to_mint = amount_sol / token_price; // Ensure you handle decimals appropriately
+#[derive(Accounts)]
+pub struct BuyTokensWithToken<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, token::mint = pledge_token_mint, token::authority = buyer)]
+    pub buyer_token_account: Box<Account<'info, TokenAccount>>, // Buyer's PledgeToken account
+    #[account(mut, token::mint = payment_mint, token::authority = buyer)]
+    pub buyer_payment_account: Box<Account<'info, TokenAccount>>, // Buyer's stablecoin account
+    #[account(mut, seeds = [ICO_SEED, pledge_token_mint.key().as_ref()], bump = ico_state.bump)]
+    pub ico_state: Box<Account<'info, ICOState>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + UserState::INIT_SPACE,
+        seeds = [USER_SEED, ico_state.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub user_state: Box<Account<'info, UserState>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + PaymentRecord::INIT_SPACE,
+        seeds = [
+            PAYMENT_SEED,
+            ico_state.key().as_ref(),
+            buyer.key().as_ref(),
+            buyer.key().as_ref()
+        ],
+        bump
+    )]
+    pub payment_record: Box<Account<'info, PaymentRecord>>,
+    #[account(mut, address = ico_state.pledge_mint, mint::authority = mint_authority)]
+    pub pledge_token_mint: Box<Account<'info, Mint>>,
+    /// CHECK: PDA that signs the MintTo CPI, never holds data
+    #[account(seeds = [MINT_AUTHORITY_SEED, ico_state.key().as_ref()], bump = ico_state.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    pub payment_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [PAYMENT_VAULT_SEED, ico_state.key().as_ref(), payment_mint.key().as_ref()],
+        bump
+    )]
+    pub payment_vault: Box<Account<'info, TokenAccount>>, // Collects this mint's payments
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: Instructions sysvar, read for the verifier's Ed25519 co-signature
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-        // Ensure the purchase doesn't exceed the total tokens allocated for sale
-        if ico_state.total_tokens_sold + tokens_to_mint > ico_state.total_tokens_allocated {
-            return Err(MyContractError::OverPurchase.into()); // Replace 0 with your error code for over-purchase
+impl<'info> BuyTokensWithToken<'info> {
+    pub fn buy_tokens_with_token(
+        ctx: Context<BuyTokensWithToken>,
+        amount: u64,
+        allowlist: Option<AllowlistProof>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.ico_state.config.batch_auction,
+            MyContractError::BatchAuctionSolOnly
+        );
+        let user_state = &mut ctx.accounts.user_state;
+        if user_state.user == Pubkey::default() {
+            user_state.user = ctx.accounts.buyer.key();
+            user_state.bump = ctx.bumps.user_state;
+        }
+        let payment_record = &mut ctx.accounts.payment_record;
+        if payment_record.payer == Pubkey::default() {
+            payment_record.beneficiary = ctx.accounts.buyer.key();
+            payment_record.payer = ctx.accounts.buyer.key();
+            payment_record.bump = ctx.bumps.payment_record;
         }
+        let payment_index = ctx
+            .accounts
+            .ico_state
+            .payment_mint_index(&ctx.accounts.payment_mint.key())?;
+        let pricing = ctx.accounts.ico_state.payment_mints[payment_index]
+            .pricing
+            .clone();
+        let quote = process_purchase(
+            &mut ctx.accounts.ico_state,
+            user_state,
+            &ctx.accounts.clock,
+            amount,
+            Some(&pricing),
+            allowlist,
+            ctx.accounts.instructions.as_ref(),
+        )?;
+        ctx.accounts.payment_record.record_payment(
+            Some(payment_index),
+            quote.cost,
+            quote.tokens,
+            false,
+        )?;
 
-        // Mint tokens to the buyer's account
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.pledge_token_mint.to_account_info(),
-            to: ctx.accounts.buyer_token_account.to_account_info(),
-            authority: ctx.accounts.ico_state.to_account_info(), // Assuming ICO contract has minting authority
+        // Move the buyer's payment tokens into the vault for that mint
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.buyer_payment_account.to_account_info(),
+            to: ctx.accounts.payment_vault.to_account_info(),
+            authority: ctx.accounts.buyer.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::mint_to(cpi_ctx, tokens_to_mint)?;
+        token::transfer(cpi_ctx, quote.cost)?;
+
+        mint_pledge(
+            &ctx.accounts.ico_state,
+            &ctx.accounts.token_program,
+            &ctx.accounts.pledge_token_mint,
+            &ctx.accounts.buyer_token_account,
+            &ctx.accounts.mint_authority,
+            quote.tokens,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClaimRefund<'info> {
+    pub claimant: Signer<'info>, // The beneficiary, or the payer once no tokens are left to burn
+    #[account(seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()], bump = ico_state.bump)]
+    pub ico_state: Box<Account<'info, ICOState>>,
+    #[account(
+        mut,
+        seeds = [
+            PAYMENT_SEED,
+            ico_state.key().as_ref(),
+            payment_record.beneficiary.as_ref(),
+            payment_record.payer.as_ref()
+        ],
+        bump = payment_record.bump
+    )]
+    pub payment_record: Box<Account<'info, PaymentRecord>>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, ico_state.key().as_ref(), payment_record.beneficiary.as_ref()],
+        bump = user_state.bump
+    )]
+    pub user_state: Box<Account<'info, UserState>>,
+    #[account(mut, address = ico_state.pledge_mint)]
+    pub pledge_token_mint: Box<Account<'info, Mint>>,
+    #[account(mut, token::mint = pledge_token_mint, token::authority = claimant)]
+    pub beneficiary_token_account: Option<Box<Account<'info, TokenAccount>>>, // Pledge tokens to burn
+    #[account(mut, seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: SystemAccount<'info>,
+    #[account(mut, address = payment_record.payer)]
+    pub payer: SystemAccount<'info>, // Receives the refund
+    #[account(mut, token::authority = sale_vault)]
+    pub payment_vault: Option<Box<Account<'info, TokenAccount>>>, // Only to refund a payment mint
+    #[account(mut, token::authority = payer)]
+    pub payer_payment_account: Option<Box<Account<'info, TokenAccount>>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimRefund<'info> {
+    /// Burns the Pledge tokens a payment record bought and returns the SOL paid
+    /// for them to the record's payer, plus what was paid in the mint of
+    /// `payment_vault` when one is passed. Payments made with several mints are
+    /// refunded one mint per call. Only the beneficiary can burn its tokens; once
+    /// none are left the payer can claim the rest itself.
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let ico_state = &ctx.accounts.ico_state;
+        require!(
+            !ico_state.is_paused(PAUSE_CLAIMS),
+            MyContractError::ClaimsPaused
+        );
+        require!(
+            ico_state.refunds_open(Clock::get()?.unix_timestamp)?,
+            MyContractError::RefundsNotOpen
+        );
+
+        let claimant = ctx.accounts.claimant.key();
+        let payment_record = &mut ctx.accounts.payment_record;
+        require!(
+            claimant == payment_record.beneficiary || claimant == payment_record.payer,
+            MyContractError::Unauthorized
+        );
+        let payment_mint = match &ctx.accounts.payment_vault {
+            Some(payment_vault) => {
+                let payer_payment_account = ctx
+                    .accounts
+                    .payer_payment_account
+                    .as_ref()
+                    .ok_or(MyContractError::PaymentMintNotAccepted)?;
+                require_keys_eq!(
+                    payer_payment_account.mint,
+                    payment_vault.mint,
+                    MyContractError::PaymentMintNotAccepted
+                );
+                Some(ico_state.payment_mint_index(&payment_vault.mint)?)
+            }
+            None => None,
+        };
+        let (burn_amount, sol_refund, token_refund) = payment_record.take_refund(payment_mint)?;
+
+        if burn_amount > 0 {
+            require_keys_eq!(
+                claimant,
+                payment_record.beneficiary,
+                MyContractError::BeneficiaryMustBurn
+            );
+            let user_state = &mut ctx.accounts.user_state;
+            user_state.amount_purchased = user_state
+                .amount_purchased
+                .checked_sub(burn_amount)
+                .ok_or(MyContractError::MathUnderflow)?;
+            let beneficiary_token_account = ctx
+                .accounts
+                .beneficiary_token_account
+                .as_ref()
+                .ok_or(MyContractError::BeneficiaryMustBurn)?;
+            let cpi_accounts = Burn {
+                mint: ctx.accounts.pledge_token_mint.to_account_info(),
+                from: beneficiary_token_account.to_account_info(),
+                authority: ctx.accounts.claimant.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            token::burn(CpiContext::new(cpi_program, cpi_accounts), burn_amount)?;
+        }
+        if sol_refund > 0 {
+            pay_from_sale_vault(
+                &ctx.accounts.ico_state,
+                &ctx.accounts.system_program,
+                &ctx.accounts.sale_vault,
+                ctx.bumps.sale_vault,
+                ctx.accounts.payer.to_account_info(),
+                sol_refund,
+            )?;
+        }
+        if let (Some(payment_vault), Some(payer_payment_account)) = (
+            &ctx.accounts.payment_vault,
+            &ctx.accounts.payer_payment_account,
+        ) {
+            if token_refund > 0 {
+                pay_from_payment_vault(
+                    &ctx.accounts.ico_state,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.sale_vault,
+                    ctx.bumps.sale_vault,
+                    payment_vault,
+                    payer_payment_account,
+                    token_refund,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SettleBatch<'info> {
+    pub claimant: Signer<'info>, // The beneficiary or the payer
+    #[account(seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()], bump = ico_state.bump)]
+    pub ico_state: Box<Account<'info, ICOState>>,
+    #[account(
+        mut,
+        seeds = [
+            PAYMENT_SEED,
+            ico_state.key().as_ref(),
+            payment_record.beneficiary.as_ref(),
+            payment_record.payer.as_ref()
+        ],
+        bump = payment_record.bump
+    )]
+    pub payment_record: Box<Account<'info, PaymentRecord>>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, ico_state.key().as_ref(), payment_record.beneficiary.as_ref()],
+        bump = user_state.bump
+    )]
+    pub user_state: Box<Account<'info, UserState>>,
+    #[account(mut, address = ico_state.pledge_mint, mint::authority = mint_authority)]
+    pub pledge_token_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = pledge_token_mint,
+        token::authority = payment_record.beneficiary
+    )]
+    pub beneficiary_token_account: Box<Account<'info, TokenAccount>>,
+    /// CHECK: PDA that signs the MintTo CPI, never holds data
+    #[account(seeds = [MINT_AUTHORITY_SEED, ico_state.key().as_ref()], bump = ico_state.mint_authority_bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(mut, seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: SystemAccount<'info>,
+    #[account(mut, address = payment_record.payer)]
+    pub payer: SystemAccount<'info>, // Receives the oversubscription refund
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-        // Update ICO and user states
-        ico_state.total_tokens_sold += tokens_to_mint;
-        user_state.amount_purchased += tokens_to_mint;
-        // Optionally update vesting_start_timestamp if necessary
+impl<'info> SettleBatch<'info> {
+    /// Mints the beneficiary's pro-rata allocation of the bids in a payment record
+    /// of a successful batch auction and refunds the part of the deposit that
+    /// didn't clear to the payer. Failed or cancelled auctions are unwound
+    /// through `claim_refund` instead.
+    pub fn settle_batch(ctx: Context<SettleBatch>) -> Result<()> {
+        let ico_state = &ctx.accounts.ico_state;
+        require!(
+            ico_state.config.batch_auction,
+            MyContractError::NotBatchAuction
+        );
+        require!(
+            !ico_state.is_paused(PAUSE_CLAIMS),
+            MyContractError::ClaimsPaused
+        );
+        match ico_state.compute_status(Clock::get()?.unix_timestamp)? {
+            SaleStatus::Ended | SaleStatus::Finalized => {}
+            SaleStatus::Cancelled => return err!(MyContractError::SaleClosed),
+            _ => return err!(MyContractError::SaleNotEnded),
+        }
+        require!(
+            ico_state.soft_cap_reached(),
+            MyContractError::SoftCapNotReached
+        );
+
+        let claimant = ctx.accounts.claimant.key();
+        let payment_record = &mut ctx.accounts.payment_record;
+        require!(
+            claimant == payment_record.beneficiary || claimant == payment_record.payer,
+            MyContractError::Unauthorized
+        );
+        let (tokens, refund) = payment_record.settle(ico_state)?;
+        ctx.accounts.user_state.record_purchase(tokens)?;
+
+        if tokens > 0 {
+            mint_pledge(
+                &ctx.accounts.ico_state,
+                &ctx.accounts.token_program,
+                &ctx.accounts.pledge_token_mint,
+                &ctx.accounts.beneficiary_token_account,
+                &ctx.accounts.mint_authority,
+                tokens,
+            )?;
+        }
+        if refund > 0 {
+            pay_from_sale_vault(
+                &ctx.accounts.ico_state,
+                &ctx.accounts.system_program,
+                &ctx.accounts.sale_vault,
+                ctx.bumps.sale_vault,
+                ctx.accounts.payer.to_account_info(),
+                refund,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct WithdrawProceeds<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump,
+        has_one = admin @ MyContractError::Unauthorized,
+        has_one = treasury @ MyContractError::Unauthorized
+    )]
+    pub ico_state: Box<Account<'info, ICOState>>,
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: SystemAccount<'info>,
+    /// CHECK: Only receives lamports, pinned to `ico_state.treasury` by `has_one`
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    #[account(mut, token::authority = sale_vault)]
+    pub payment_vault: Option<Box<Account<'info, TokenAccount>>>, // Only to withdraw a payment mint
+    #[account(mut, token::authority = treasury)]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawProceeds<'info> {
+    /// Moves `amount` of the proceeds to the treasury: lamports from the sale
+    /// vault, or base units of a payment mint when `payment_vault` is passed.
+    pub fn withdraw_proceeds(ctx: Context<WithdrawProceeds>, amount: u64) -> Result<()> {
+        let ico_state = &mut ctx.accounts.ico_state;
+        require!(
+            !ico_state.is_paused(PAUSE_TREASURY),
+            MyContractError::TreasuryPaused
+        );
+        require!(
+            ico_state.can_withdraw_proceeds(),
+            MyContractError::SaleNotFinalized
+        );
+        ico_state.proceeds_withdrawn = true;
+
+        let (payment_mint, destination) = match &ctx.accounts.payment_vault {
+            Some(payment_vault) => {
+                let treasury_token_account = ctx
+                    .accounts
+                    .treasury_token_account
+                    .as_ref()
+                    .ok_or(MyContractError::PaymentMintNotAccepted)?;
+                require_keys_eq!(
+                    treasury_token_account.mint,
+                    payment_vault.mint,
+                    MyContractError::PaymentMintNotAccepted
+                );
+                pay_from_payment_vault(
+                    &ctx.accounts.ico_state,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.sale_vault,
+                    ctx.bumps.sale_vault,
+                    payment_vault,
+                    treasury_token_account,
+                    amount,
+                )?;
+                (Some(payment_vault.mint), treasury_token_account.key())
+            }
+            None => {
+                ctx.accounts.ico_state.record_withdrawal(amount)?;
+                pay_from_sale_vault(
+                    &ctx.accounts.ico_state,
+                    &ctx.accounts.system_program,
+                    &ctx.accounts.sale_vault,
+                    ctx.bumps.sale_vault,
+                    ctx.accounts.treasury.to_account_info(),
+                    amount,
+                )?;
+                (None, ctx.accounts.treasury.key())
+            }
+        };
 
+        emit!(ProceedsWithdrawn {
+            ico_state: ctx.accounts.ico_state.key(),
+            payment_mint,
+            amount,
+            destination,
+        });
         Ok(())
     }
 }
 
-fn determine_current_phase(ico_state: &ICOState, clock: &Sysvar<Clock>) -> u8 {
+#[derive(Accounts)]
+pub struct AddPaymentMint<'info> {
+    #[account(
+        mut,
+        seeds = [ICO_SEED, ico_state.pledge_mint.as_ref()],
+        bump = ico_state.bump,
+        has_one = admin @ MyContractError::Unauthorized
+    )]
+    pub ico_state: Account<'info, ICOState>,
+    pub payment_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [PAYMENT_VAULT_SEED, ico_state.key().as_ref(), payment_mint.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = sale_vault
+    )]
+    pub payment_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA that owns every payment vault, same address as the SOL vault
+    #[account(seeds = [SALE_VAULT_SEED, ico_state.key().as_ref()], bump)]
+    pub sale_vault: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+}
+
+/// Runs every sale check for a payment of `amount` (lamports, or base units of a
+/// payment mint priced by `pricing`) and records the purchase on both state
+/// accounts. Returns the tokens to mint and what to collect from the buyer.
+fn process_purchase(
+    ico_state: &mut Account<ICOState>,
+    user_state: &mut Account<UserState>,
+    clock: &Clock,
+    amount: u64,
+    pricing: Option<&PaymentPricing>,
+    allowlist: Option<AllowlistProof>,
+    instructions: Option<&UncheckedAccount>,
+) -> Result<Quote> {
     let now = clock.unix_timestamp;
-    let mut elapsed_time = now - ico_state.start_timestamp;
+    require!(
+        !ico_state.is_paused(PAUSE_SALES),
+        MyContractError::SalePaused
+    );
+
+    // Advance to the current phase
+    let current_phase = match sync_status(ico_state, now)? {
+        SaleStatus::Active(phase) => phase,
+        _ => return err!(MyContractError::SaleNotActive),
+    };
+
+    // Private phases only sell to wallets in their Merkle allowlist
+    let mut allowlist_cap = 0;
+    if let Some(root) = ico_state.phase_details[current_phase as usize].allowlist_root {
+        let allowlist = allowlist.ok_or(MyContractError::NotAllowlisted)?;
+        let leaf = merkle::leaf(&user_state.user, allowlist.cap);
+        require!(
+            merkle::verify(&allowlist.proof, &root, leaf),
+            MyContractError::NotAllowlisted
+        );
+        allowlist_cap = allowlist.cap;
+    }
+
+    // Anti-bot protections of the phase
+    let protection = ico_state.phase_details[current_phase as usize].bot_protection;
+    if let Some(verifier) = protection.verifier {
+        let instructions = instructions.ok_or(MyContractError::MissingVerifierSignature)?;
+        attestation::verify(
+            instructions,
+            &verifier,
+            &ico_state.key(),
+            &user_state.user,
+            now,
+        )?;
+    }
+    user_state.record_purchase_time(now, protection.wallet_cooldown)?;
+    ico_state.record_slot_purchase(clock.slot, protection.max_purchases_per_slot)?;
+
+    let batch_auction = ico_state.config.batch_auction;
+    let phase_remaining = ico_state.phase_details[current_phase as usize]
+        .tokens_remaining()
+        .min(
+            ico_state
+                .total_tokens_allocated
+                .saturating_sub(ico_state.total_tokens_sold),
+        );
+    let (quote, phase_tokens) = if batch_auction {
+        // Bids only commit funds; allocations are worked out at settlement
+        let quote = pricing::commit_order(ico_state, current_phase, amount, pricing, now)?;
+        ico_state.record_commitment(quote.tokens, quote.cost)?;
+        let mut phase_tokens = [0; MAX_PHASES];
+        phase_tokens[current_phase as usize] = quote.tokens;
+        (quote, phase_tokens)
+    } else {
+        // Price the payment against the phase caps; whatever can't buy a whole
+        // base unit stays with the buyer
+        let fill = pricing::fill_order(ico_state, current_phase, amount, pricing, now)?;
+
+        // Ensure the purchase doesn't exceed the total tokens allocated for sale
+        ico_state.record_sale(fill.quote.tokens)?;
+        (fill.quote, fill.phase_tokens)
+    };
+    ico_state.check_min_purchase(quote.tokens, phase_remaining)?;
+    let max_per_wallet = ico_state.config.max_per_wallet;
 
-    for (i, phase) in ico_state.phase_details.iter().enumerate() {
-        if elapsed_time <= phase.duration {
-            return i as u8;
+    // Update the user's state and hold it to the per-wallet limits
+    let wallet_total = if batch_auction {
+        user_state.record_commitment(quote.tokens)?
+    } else {
+        user_state.record_purchase(quote.tokens)?;
+        user_state.amount_purchased
+    };
+    require!(
+        max_per_wallet == 0 || wallet_total <= max_per_wallet,
+        MyContractError::WalletLimitExceeded
+    );
+    // Tokens spilled into later phases count against those phases' limits
+    for (index, &tokens) in phase_tokens.iter().enumerate() {
+        if tokens == 0 {
+            continue;
         }
-        elapsed_time -= phase.duration;
+        ico_state.record_phase_purchase(index as u8)?;
+        user_state.record_phase_purchase(index as u8, tokens)?;
+        let phase_max_per_wallet = ico_state.phase_details[index].max_per_wallet;
+        require!(
+            phase_max_per_wallet == 0 || user_state.phase_purchased[index] <= phase_max_per_wallet,
+            MyContractError::PhaseWalletLimitExceeded
+        );
+        require!(
+            allowlist_cap == 0 || user_state.phase_purchased[index] <= allowlist_cap,
+            MyContractError::AllowlistCapExceeded
+        );
+    }
+    // Optionally update vesting_start_timestamp if necessary
+
+    // Selling out ends the sale without waiting for the last phase to run out
+    sync_status(ico_state, now)?;
+    Ok(quote)
+}
+
+/// Mints `amount` Pledge tokens to `to`, signed by the ICO's mint authority PDA.
+fn mint_pledge<'info>(
+    ico_state: &Account<'info, ICOState>,
+    token_program: &Program<'info, Token>,
+    pledge_token_mint: &Account<'info, Mint>,
+    to: &Account<'info, TokenAccount>,
+    mint_authority: &UncheckedAccount<'info>,
+    amount: u64,
+) -> Result<()> {
+    let ico_key = ico_state.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        MINT_AUTHORITY_SEED,
+        ico_key.as_ref(),
+        &[ico_state.mint_authority_bump],
+    ]];
+    let cpi_accounts = MintTo {
+        mint: pledge_token_mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: mint_authority.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    token::mint_to(cpi_ctx, amount)
+}
+
+/// Sends `amount` lamports out of the sale vault, signed by its PDA. The
+/// vault's rent-exempt reserve is never paid out.
+fn pay_from_sale_vault<'info>(
+    ico_state: &Account<'info, ICOState>,
+    system_program: &Program<'info, System>,
+    sale_vault: &SystemAccount<'info>,
+    sale_vault_bump: u8,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let available = sale_vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0));
+    require!(amount <= available, MyContractError::SaleVaultReserve);
+    let ico_key = ico_state.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[SALE_VAULT_SEED, ico_key.as_ref(), &[sale_vault_bump]]];
+    let cpi_accounts = system_program::Transfer {
+        from: sale_vault.to_account_info(),
+        to,
+    };
+    let cpi_program = system_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    system_program::transfer(cpi_ctx, amount)
+}
+
+/// Sends `amount` base units out of a payment vault, signed by the sale vault PDA.
+fn pay_from_payment_vault<'info>(
+    ico_state: &Account<'info, ICOState>,
+    token_program: &Program<'info, Token>,
+    sale_vault: &SystemAccount<'info>,
+    sale_vault_bump: u8,
+    payment_vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    amount: u64,
+) -> Result<()> {
+    let ico_key = ico_state.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[SALE_VAULT_SEED, ico_key.as_ref(), &[sale_vault_bump]]];
+    let cpi_accounts = Transfer {
+        from: payment_vault.to_account_info(),
+        to: to.to_account_info(),
+        authority: sale_vault.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Stores the clock-derived sale status on `ico_state`, moving `current_phase`
+/// forward and emitting `PhaseChanged` whenever a new phase becomes active.
+fn sync_status(ico_state: &mut Account<ICOState>, now: i64) -> Result<SaleStatus> {
+    let status = ico_state.compute_status(now)?;
+    if status == ico_state.status {
+        return Ok(status);
     }
 
-    // If the time exceeds all phase durations, return the last phase by default
-    // or handle as needed for your logic (e.g., ICO has ended)
-    (ico_state.phase_details.len() - 1) as u8
+    if let SaleStatus::Active(phase) = status {
+        ico_state.current_phase = phase;
+        ico_state.phase_started_at = ico_state.phase_start(phase)?;
+        emit!(PhaseChanged {
+            ico_state: ico_state.key(),
+            phase,
+            token_price: ico_state.phase_details[phase as usize].token_price,
+            phase_started_at: ico_state.phase_started_at,
+        });
+    }
+    ico_state.status = status;
+    Ok(status)
 }
@@ -1,6 +1,8 @@
 
 use anchor_lang::prelude::*;
 
+use crate::rewards;
+
 #[account]
 pub struct MLMParticipant {
     pub user: Pubkey,
@@ -22,7 +24,6 @@ impl MLMStrategy {
 
     pub fn calculate_rewards(participant: AccountInfo) -> Result<u64> {
         let participant_data = MLMParticipant::try_from_slice(&participant.data.borrow())?;
-        let rewards = participant_data.level as u64 * 100; 
-        Ok(rewards)
+        rewards::mlm_rewards(participant_data.level)
     }
 }
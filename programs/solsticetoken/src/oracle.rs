@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+
+use crate::error::MyContractError;
+use crate::state::{ConversionRate, OracleConfig, BASIS_POINTS};
+
+// Layout of a Pyth v2 price account, as laid out by pyth-sdk-solana's `PriceAccount`
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+pub const PYTH_VERSION: u32 = 2;
+pub const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPO_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+pub const PYTH_PRICE_MIN_LEN: usize = 240;
+
+// Phase prices are quoted in micro-USD when an oracle is configured
+const QUOTE_DECIMALS: i32 = 6;
+const SOL_DECIMALS: i32 = 9;
+
+/// Aggregate price published in a Pyth price account: `price * 10^expo` USD per SOL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+pub fn read_price(data: &[u8]) -> Result<OraclePrice> {
+    require!(
+        data.len() >= PYTH_PRICE_MIN_LEN
+            && read_u32(data, MAGIC_OFFSET) == PYTH_MAGIC
+            && read_u32(data, VERSION_OFFSET) == PYTH_VERSION
+            && read_u32(data, ACCOUNT_TYPE_OFFSET) == PYTH_ACCOUNT_TYPE_PRICE,
+        MyContractError::InvalidOracleAccount
+    );
+    require!(
+        read_u32(data, AGG_STATUS_OFFSET) == PYTH_STATUS_TRADING,
+        MyContractError::OraclePriceUnavailable
+    );
+
+    Ok(OraclePrice {
+        price: i64::from_le_bytes(
+            data[AGG_PRICE_OFFSET..AGG_PRICE_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        ),
+        conf: u64::from_le_bytes(
+            data[AGG_CONF_OFFSET..AGG_CONF_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        ),
+        expo: i32::from_le_bytes(data[EXPO_OFFSET..EXPO_OFFSET + 4].try_into().unwrap()),
+        publish_time: i64::from_le_bytes(
+            data[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        ),
+    })
+}
+
+impl OraclePrice {
+    /// Rejects prices that are non-positive, older than `max_age` seconds, or whose
+    /// confidence interval is wider than `max_confidence_bps` of the price.
+    pub fn validate(&self, now: i64, config: &OracleConfig) -> Result<()> {
+        require!(self.price > 0, MyContractError::OraclePriceUnavailable);
+        let age = now
+            .checked_sub(self.publish_time)
+            .ok_or(MyContractError::MathUnderflow)?;
+        require!(age <= config.max_age, MyContractError::OraclePriceStale);
+
+        let max_conf = (self.price as u128)
+            .checked_mul(config.max_confidence_bps as u128)
+            .ok_or(MyContractError::MathOverflow)?
+            / BASIS_POINTS as u128;
+        require!(
+            self.conf as u128 <= max_conf,
+            MyContractError::OracleConfidenceTooWide
+        );
+        Ok(())
+    }
+
+    /// Lamports to micro-USD conversion implied by this price.
+    pub fn lamports_to_usd(&self) -> Result<ConversionRate> {
+        // micro-USD per lamport = price * 10^(expo + QUOTE_DECIMALS - SOL_DECIMALS)
+        let scale = self
+            .expo
+            .checked_add(QUOTE_DECIMALS - SOL_DECIMALS)
+            .ok_or(MyContractError::InvalidOracleAccount)?;
+        let factor = 10u64
+            .checked_pow(scale.unsigned_abs())
+            .ok_or(MyContractError::InvalidOracleAccount)?;
+
+        let price = self.price as u64;
+        Ok(if scale < 0 {
+            ConversionRate {
+                payment_units: factor,
+                quote_units: price,
+            }
+        } else {
+            ConversionRate {
+                payment_units: 1,
+                quote_units: price
+                    .checked_mul(factor)
+                    .ok_or(MyContractError::MathOverflow)?,
+            }
+        })
+    }
+}
+
+/// Reads and validates `feed` against `config`, returning the SOL to USD rate.
+pub fn sol_usd_rate(feed: &AccountInfo, config: &OracleConfig, now: i64) -> Result<ConversionRate> {
+    require_keys_eq!(feed.key(), config.feed, MyContractError::OracleFeedMismatch);
+    let price = read_price(&feed.try_borrow_data()?)?;
+    price.validate(now, config)?;
+    price.lamports_to_usd()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
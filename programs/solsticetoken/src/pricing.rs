@@ -0,0 +1,536 @@
+use anchor_lang::prelude::*;
+
+use crate::error::MyContractError;
+use crate::state::{
+    ConversionRate, ICOState, PaymentPricing, PhaseDetail, PhaseOverflow, PricingCurve,
+    RoundingPolicy, TokenPrice, BASIS_POINTS, MAX_PHASES,
+};
+
+const TOKEN_UNIT: u128 = 1_000_000_000; // Base units per whole Pledge token
+const CURVE_SCALE: u128 = 1_000_000_000_000; // Fixed point scale of curve growth factors
+
+/// What a buyer receives for a payment and what it actually costs them, in
+/// lamports or base units of the payment mint. Anything paid beyond `cost` could
+/// not buy a whole base unit and is never collected.
+#[derive(Debug)]
+pub struct Quote {
+    pub tokens: u64,
+    pub cost: u64,
+}
+
+/// A filled order and the base units it took out of each phase, so per-phase
+/// limits can be held to what was actually bought in that phase.
+#[derive(Debug)]
+pub struct Fill {
+    pub quote: Quote,
+    pub phase_tokens: [u64; MAX_PHASES],
+}
+
+/// Every base unit is paid for in full, so a payment that doesn't cover the
+/// tokens `rounding` rounded up to is refused.
+pub fn quote(price: &TokenPrice, amount: u64, rounding: RoundingPolicy) -> Result<Quote> {
+    let tokens = tokens_for(price, amount, rounding)?;
+    require!(tokens > 0, MyContractError::PurchaseTooSmall);
+
+    let cost = lamports_for(price, tokens)?;
+    require!(cost <= amount, MyContractError::InsufficientPayment);
+    Ok(Quote { tokens, cost })
+}
+
+/// Prices a payment made during `phase`, respecting each phase's allocation and
+/// recording what was sold against it and returning what came out of each. Depending on `phase_overflow`, a purchase
+/// that doesn't fit either fails, is cut down to what is left, or continues into
+/// the following phases that admit the same buyers. Phases are priced as of `now`. `pricing` converts phase
+/// prices into the payment currency; `None` pays in the currency phases are
+/// quoted in.
+pub fn fill_order(
+    ico_state: &mut ICOState,
+    phase: u8,
+    amount: u64,
+    pricing: Option<&PaymentPricing>,
+    now: i64,
+) -> Result<Fill> {
+    let config = ico_state.config;
+    if let Some(curve) = config.pricing_curve {
+        return fill_curve_order(ico_state, &curve, phase, amount, pricing);
+    }
+    let phase_prices = ico_state.phase_prices(now)?;
+    let price_of = |index: usize| match pricing {
+        Some(pricing) => pricing.price(index, &phase_prices[index]),
+        None => Ok(phase_prices[index]),
+    };
+    let mut sale_remaining = ico_state
+        .total_tokens_allocated
+        .saturating_sub(ico_state.total_tokens_sold);
+
+    let mut phase_tokens = [0; MAX_PHASES];
+    let index = phase as usize;
+    if config.phase_overflow != PhaseOverflow::SpillToNext {
+        let current = &mut ico_state.phase_details[index];
+        let price = price_of(index)?;
+        let mut quote = quote(&price, amount, config.rounding)?;
+        if config.phase_overflow == PhaseOverflow::PartialFill {
+            // The last buyer of a phase gets what is left and pays only for that
+            let available = current.tokens_remaining().min(sale_remaining);
+            require!(available > 0, MyContractError::PhaseCapExceeded);
+            if quote.tokens > available {
+                quote = Quote {
+                    tokens: available,
+                    cost: lamports_for(&price, available)?,
+                };
+            }
+        }
+        require!(
+            quote.tokens <= current.tokens_remaining(),
+            MyContractError::PhaseCapExceeded
+        );
+        current.tokens_sold = current
+            .tokens_sold
+            .checked_add(quote.tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        phase_tokens[index] = quote.tokens;
+        return Ok(Fill {
+            quote,
+            phase_tokens,
+        });
+    }
+
+    let end = spill_end(&ico_state.phase_details, index);
+    let mut filled = Quote { tokens: 0, cost: 0 };
+    let mut amount_left = amount;
+    for (index, next) in ico_state
+        .phase_details
+        .iter_mut()
+        .enumerate()
+        .take(end)
+        .skip(index)
+    {
+        if sale_remaining == 0 {
+            break;
+        }
+        let available = next.tokens_remaining().min(sale_remaining);
+        if available == 0 {
+            continue;
+        }
+        let price = price_of(index)?;
+        let tokens = tokens_for(&price, amount_left, config.rounding)?;
+        if tokens == 0 {
+            break;
+        }
+
+        let take = tokens.min(available);
+        let cost = lamports_for(&price, take)?;
+        require!(cost <= amount_left, MyContractError::InsufficientPayment);
+        next.tokens_sold = next
+            .tokens_sold
+            .checked_add(take)
+            .ok_or(MyContractError::MathOverflow)?;
+        phase_tokens[index] = take;
+        filled.tokens = filled
+            .tokens
+            .checked_add(take)
+            .ok_or(MyContractError::MathOverflow)?;
+        filled.cost = filled
+            .cost
+            .checked_add(cost)
+            .ok_or(MyContractError::MathOverflow)?;
+        amount_left = amount_left
+            .checked_sub(cost)
+            .ok_or(MyContractError::MathUnderflow)?;
+        sale_remaining -= take;
+        if take == tokens {
+            break;
+        }
+    }
+
+    require!(filled.tokens > 0, MyContractError::PhaseCapExceeded);
+    Ok(Fill {
+        quote: filled,
+        phase_tokens,
+    })
+}
+
+/// End of the run of phases from `phase` on that admit the buyers `phase` does.
+/// An order never spills past it, since the buyer was only checked against the
+/// allowlist and verifier of `phase`.
+fn spill_end(phases: &[PhaseDetail], phase: usize) -> usize {
+    let current = &phases[phase];
+    phases[phase..]
+        .iter()
+        .position(|next| !current.admits_same_buyers(next))
+        .map_or(phases.len(), |run| phase + run)
+}
+
+/// Prices a batch auction bid made during `phase` at that phase's current price.
+/// Bids aren't held to phase allocations; oversubscription is settled pro rata.
+pub fn commit_order(
+    ico_state: &ICOState,
+    phase: u8,
+    amount: u64,
+    pricing: Option<&PaymentPricing>,
+    now: i64,
+) -> Result<Quote> {
+    let index = phase as usize;
+    let phase_price = ico_state.phase_prices(now)?[index];
+    let price = match pricing {
+        Some(pricing) => pricing.price(index, &phase_price)?,
+        None => phase_price,
+    };
+    quote(&price, amount, ico_state.config.rounding)
+}
+
+/// Prices a payment against the sale's bonding curve. Phases still cap what can
+/// be sold, following `phase_overflow`; a converted payment mint pays the curve
+/// price through its conversion rate.
+fn fill_curve_order(
+    ico_state: &mut ICOState,
+    curve: &PricingCurve,
+    phase: u8,
+    amount: u64,
+    pricing: Option<&PaymentPricing>,
+) -> Result<Fill> {
+    let rate = match pricing {
+        None => None,
+        Some(PaymentPricing::Converted(rate)) => Some(*rate),
+        Some(PaymentPricing::PerPhase { .. }) => return err!(MyContractError::InvalidPrice),
+    };
+    let budget = match rate {
+        Some(rate) => to_quote_units(&rate, amount)?,
+        None => amount,
+    };
+
+    let config = ico_state.config;
+    let sold = ico_state.total_tokens_sold;
+    let sale_remaining = ico_state.total_tokens_allocated.saturating_sub(sold);
+    let end = spill_end(&ico_state.phase_details, phase as usize);
+    let phases = &mut ico_state.phase_details[phase as usize..end];
+    let available = if config.phase_overflow == PhaseOverflow::SpillToNext {
+        phases
+            .iter()
+            .try_fold(0u64, |sum, p| sum.checked_add(p.tokens_remaining()))
+            .ok_or(MyContractError::MathOverflow)?
+    } else {
+        phases[0].tokens_remaining()
+    }
+    .min(sale_remaining);
+    require!(available > 0, MyContractError::PhaseCapExceeded);
+
+    let mut tokens = curve_tokens_for(curve, sold, budget, available.saturating_add(1))?;
+    if tokens > available {
+        require!(
+            config.phase_overflow != PhaseOverflow::Reject,
+            MyContractError::PhaseCapExceeded
+        );
+        tokens = available;
+    }
+    require!(tokens > 0, MyContractError::PurchaseTooSmall);
+
+    let cost = curve_cost(curve, sold, tokens)?;
+    let cost = match rate {
+        Some(rate) => from_quote_units(&rate, cost)?,
+        None => cost,
+    }
+    .min(amount);
+
+    // Count the tokens against the phases they came out of
+    let mut phase_tokens = [0; MAX_PHASES];
+    let mut left = tokens;
+    for (taken, next) in phase_tokens[phase as usize..].iter_mut().zip(phases) {
+        let take = left.min(next.tokens_remaining());
+        next.tokens_sold = next
+            .tokens_sold
+            .checked_add(take)
+            .ok_or(MyContractError::MathOverflow)?;
+        *taken = take;
+        left -= take;
+        if left == 0 {
+            break;
+        }
+    }
+    Ok(Fill {
+        quote: Quote { tokens, cost },
+        phase_tokens,
+    })
+}
+
+/// Price, rounded up, of buying `tokens` base units along `curve` once `sold`
+/// base units have been sold: the curve integrated over the quantity bought.
+pub fn curve_cost(curve: &PricingCurve, sold: u64, tokens: u64) -> Result<u64> {
+    let (sold, tokens) = (sold as u128, tokens as u128);
+    let cost = match *curve {
+        PricingCurve::Linear { start_price, slope } => {
+            // start * q + slope * ((s + q)^2 - s^2) / 2, with q and s in whole tokens
+            let base = ceil_div(checked_mul(start_price as u128, tokens)?, TOKEN_UNIT)?;
+            let sold_after = sold
+                .checked_mul(2)
+                .and_then(|s| s.checked_add(tokens))
+                .ok_or(MyContractError::MathOverflow)?;
+            let area = ceil_div(checked_mul(tokens, sold_after)?, TOKEN_UNIT)?;
+            let growth = ceil_div(checked_mul(area, slope as u128)?, 2 * TOKEN_UNIT)?;
+            base.checked_add(growth)
+                .ok_or(MyContractError::MathOverflow)?
+        }
+        PricingCurve::Exponential { step, .. } => {
+            let step = step as u128;
+            let summed = |segments: u128| segments_price(curve, segments);
+            let price = |segment: u128| segment_price(curve, segment);
+
+            let end = sold
+                .checked_add(tokens)
+                .ok_or(MyContractError::MathOverflow)?;
+            let (first, last) = (sold / step, end / step);
+            let spend = if first == last {
+                checked_mul(price(first)?, tokens)?
+            } else {
+                // Partial first segment, the whole segments in between, then
+                // the partial last segment
+                let head = checked_mul(price(first)?, (first + 1) * step - sold)?;
+                let series = summed(last)?
+                    .checked_sub(summed(first + 1)?)
+                    .ok_or(MyContractError::MathUnderflow)?;
+                let middle = checked_mul(series, step)?;
+                let tail = checked_mul(price(last)?, end - last * step)?;
+                head.checked_add(middle)
+                    .and_then(|sum| sum.checked_add(tail))
+                    .ok_or(MyContractError::MathOverflow)?
+            };
+            ceil_div(spend, TOKEN_UNIT)?
+        }
+    };
+    Ok(u64::try_from(cost).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+/// Whole-token price of an exponential curve's first `segments` segments summed
+/// as a geometric series. Each segment's price is the difference of two sums, so
+/// pricing segment by segment or in one go agrees exactly.
+fn segments_price(curve: &PricingCurve, segments: u128) -> Result<u128> {
+    let PricingCurve::Exponential {
+        start_price,
+        growth_bps,
+        ..
+    } = *curve
+    else {
+        return err!(MyContractError::InvalidPrice);
+    };
+    let factor = CURVE_SCALE * (BASIS_POINTS as u128 + growth_bps as u128) / BASIS_POINTS as u128;
+    ceil_div(
+        checked_mul(
+            start_price as u128,
+            pow_scaled(factor, segments)? - CURVE_SCALE,
+        )?,
+        factor - CURVE_SCALE,
+    )
+}
+
+/// Whole-token price of an exponential curve's segment `segment`.
+fn segment_price(curve: &PricingCurve, segment: u128) -> Result<u128> {
+    Ok(segments_price(curve, segment + 1)?
+        .checked_sub(segments_price(curve, segment)?)
+        .ok_or(MyContractError::MathUnderflow)?)
+}
+
+/// Most base units, at most `max_tokens`, that `budget` buys along `curve`
+/// once `sold` base units have been sold; may be zero. The curve is inverted in
+/// closed form, which only misses by how costs round, so the exact answer is
+/// searched for around that estimate rather than across all of `max_tokens`.
+pub fn curve_tokens_for(
+    curve: &PricingCurve,
+    sold: u64,
+    budget: u64,
+    max_tokens: u64,
+) -> Result<u64> {
+    // Quantities too large to price are more than any budget can pay for
+    let affordable = |tokens: u64| curve_cost(curve, sold, tokens).is_ok_and(|cost| cost <= budget);
+    let estimate = match *curve {
+        PricingCurve::Linear { start_price, slope } => {
+            linear_tokens_for(start_price, slope, sold, budget)
+        }
+        PricingCurve::Exponential { step, .. } => {
+            exponential_tokens_for(curve, step, sold, budget, max_tokens)
+        }
+    };
+
+    // Stride away from the estimate, doubling each time, until the answer is
+    // bracketed, then bisect. Buying nothing always fits the budget.
+    let guess = estimate.unwrap_or(0).min(max_tokens);
+    let (mut low, mut high) = if affordable(guess) {
+        let (mut low, mut stride) = (guess, 1u64);
+        loop {
+            let next = low.saturating_add(stride).min(max_tokens);
+            if next == low {
+                return Ok(max_tokens);
+            }
+            if !affordable(next) {
+                break (low, next);
+            }
+            low = next;
+            stride = stride.saturating_mul(2);
+        }
+    } else {
+        let (mut high, mut stride) = (guess, 1u64);
+        loop {
+            let next = high.saturating_sub(stride);
+            if next == 0 || affordable(next) {
+                break (next, high);
+            }
+            high = next;
+            stride = stride.saturating_mul(2);
+        }
+    };
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if affordable(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+/// Base units `budget` buys along a linear curve, before rounding: the root of
+/// `slope * q^2 / 2 + price * q = budget` for `q` whole tokens at the current
+/// price. `None` if the terms overflow.
+fn linear_tokens_for(start_price: u64, slope: u64, sold: u64, budget: u64) -> Option<u64> {
+    let price = (start_price as u128).checked_add(slope as u128 * sold as u128 / TOKEN_UNIT)?;
+    let discriminant = price
+        .checked_mul(price)?
+        .checked_add((2 * slope as u128).checked_mul(budget as u128)?)?;
+    // The root in the form that doesn't cancel, with the budget in base units
+    let tokens = 2 * budget as u128 * TOKEN_UNIT / (price + isqrt(discriminant));
+    Some(tokens.min(u64::MAX as u128) as u64)
+}
+
+/// Base units `budget` buys along an exponential curve, before rounding. The
+/// last segment boundary the budget reaches is searched for by its exact cost,
+/// at most one step per doubling of the segments within `max_tokens`, and what
+/// is left buys into the next segment at its price. `None` if a segment can't
+/// be priced.
+fn exponential_tokens_for(
+    curve: &PricingCurve,
+    step: u64,
+    sold: u64,
+    budget: u64,
+    max_tokens: u64,
+) -> Option<u64> {
+    let first = sold / step;
+    // Base units up to the end of the `k`th segment after the current one, and their cost
+    let boundary = |k: u64| -> Option<(u64, u64)> {
+        let tokens = first.checked_add(k + 1)?.checked_mul(step)? - sold;
+        let cost = curve_cost(curve, sold, tokens).ok()?;
+        Some((tokens, cost))
+    };
+    let reached = |k: u64| boundary(k).filter(|&(_, cost)| cost <= budget);
+
+    let (tokens, cost, segment) = match reached(0) {
+        None => (0, 0, first),
+        Some(mut end) => {
+            let (mut low, mut high) = (0, sold.saturating_add(max_tokens) / step - first);
+            while low < high {
+                let mid = low + (high - low).div_ceil(2);
+                match reached(mid) {
+                    Some(mid_end) => (low, end) = (mid, mid_end),
+                    None => high = mid - 1,
+                }
+            }
+            (end.0, end.1, first + low + 1)
+        }
+    };
+    let price = segment_price(curve, segment as u128).ok()?;
+    let more = (budget - cost) as u128 * TOKEN_UNIT / price.max(1);
+    Some(tokens.saturating_add(more.min(u64::MAX as u128) as u64))
+}
+
+/// Integer square root, rounded down.
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method from above converges down onto the root
+    let mut root = 1u128 << (u128::BITS - n.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (root + n / root) / 2;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+fn to_quote_units(rate: &ConversionRate, amount: u64) -> Result<u64> {
+    let units = checked_mul(amount as u128, rate.quote_units as u128)? / rate.payment_units as u128;
+    Ok(u64::try_from(units).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+fn from_quote_units(rate: &ConversionRate, amount: u64) -> Result<u64> {
+    let units = ceil_div(
+        checked_mul(amount as u128, rate.payment_units as u128)?,
+        rate.quote_units as u128,
+    )?;
+    Ok(u64::try_from(units).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+/// `factor^exponent` for a factor scaled by `CURVE_SCALE`.
+fn pow_scaled(mut factor: u128, mut exponent: u128) -> Result<u128> {
+    let mut result = CURVE_SCALE;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = checked_mul(result, factor)? / CURVE_SCALE;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            factor = checked_mul(factor, factor)? / CURVE_SCALE;
+        }
+    }
+    Ok(result)
+}
+
+fn checked_mul(a: u128, b: u128) -> Result<u128> {
+    Ok(a.checked_mul(b).ok_or(MyContractError::MathOverflow)?)
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Result<u128> {
+    divide(numerator, denominator, RoundingPolicy::Up)
+}
+
+/// Base units bought by `amount_lamports`; may be zero.
+pub fn tokens_for(
+    price: &TokenPrice,
+    amount_lamports: u64,
+    rounding: RoundingPolicy,
+) -> Result<u64> {
+    require!(
+        price.tokens > 0 && price.lamports > 0,
+        MyContractError::InvalidPrice
+    );
+
+    let numerator = (amount_lamports as u128)
+        .checked_mul(price.tokens as u128)
+        .ok_or(MyContractError::MathOverflow)?;
+    let tokens = divide(numerator, price.lamports as u128, rounding)?;
+    Ok(u64::try_from(tokens).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+/// Lamports needed to buy `tokens` base units, rounded up in favour of the sale.
+pub fn lamports_for(price: &TokenPrice, tokens: u64) -> Result<u64> {
+    require!(
+        price.tokens > 0 && price.lamports > 0,
+        MyContractError::InvalidPrice
+    );
+
+    let numerator = (tokens as u128)
+        .checked_mul(price.lamports as u128)
+        .ok_or(MyContractError::MathOverflow)?;
+    let lamports = divide(numerator, price.tokens as u128, RoundingPolicy::Up)?;
+    Ok(u64::try_from(lamports).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+fn divide(numerator: u128, denominator: u128, rounding: RoundingPolicy) -> Result<u128> {
+    let adjusted = match rounding {
+        RoundingPolicy::Down => Some(numerator),
+        RoundingPolicy::Nearest => numerator.checked_add(denominator / 2),
+        RoundingPolicy::Up => numerator.checked_add(denominator - 1),
+    }
+    .ok_or(MyContractError::MathOverflow)?;
+    Ok(adjusted / denominator)
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::error::MyContractError;
+
+// Reward arithmetic shared with the standalone vesting, MLM and treasury
+// programs, kept here so it is built and tested with the sale.
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+pub const VESTING_PERIOD: i64 = 2 * SECONDS_PER_YEAR as i64;
+pub const REWARD_MULTIPLIER: u64 = 40; // Rewards per vested token
+pub const MLM_REWARD_PER_LEVEL: u64 = 100;
+
+pub fn vesting_end(start: i64) -> Result<i64> {
+    Ok(start
+        .checked_add(VESTING_PERIOD)
+        .ok_or(MyContractError::MathOverflow)?)
+}
+
+/// Rewards still claimable for `amount_purchased` vested tokens.
+pub fn rewards_due(amount_purchased: u64, rewards_claimed: u64) -> Result<u64> {
+    let total_rewards = amount_purchased
+        .checked_mul(REWARD_MULTIPLIER)
+        .ok_or(MyContractError::MathOverflow)?;
+    Ok(total_rewards
+        .checked_sub(rewards_claimed)
+        .ok_or(MyContractError::MathUnderflow)?)
+}
+
+/// Rewards accrued by `amount` locked tokens between `from` and `now`.
+pub fn accrued_rewards(amount: u64, from: i64, now: i64) -> Result<u64> {
+    let elapsed = now
+        .checked_sub(from)
+        .ok_or(MyContractError::MathUnderflow)?;
+    let elapsed = u64::try_from(elapsed).map_err(|_| MyContractError::MathUnderflow)?;
+    let accrued = (amount as u128) * (elapsed as u128) / (SECONDS_PER_YEAR as u128);
+    Ok(u64::try_from(accrued).map_err(|_| MyContractError::MathOverflow)?)
+}
+
+/// Pledge tokens bought with `amount_usd` at `sale_level`, 0 for an unknown level.
+pub fn pledge_tokens_for(amount_usd: u64, sale_level: u8) -> Result<u64> {
+    let (numerator, denominator) = match sale_level {
+        1 => (2, 1),
+        2 => (175, 100),
+        3 => (15, 10),
+        4 => (125, 100),
+        5 => (1, 1), // Level 5 offers 1 Pledge token per $1
+        _ => (0, 1), // Default case if the sale level is not recognized
+    };
+    Ok(amount_usd
+        .checked_mul(numerator)
+        .ok_or(MyContractError::MathOverflow)?
+        / denominator)
+}
+
+pub fn mlm_rewards(level: u8) -> Result<u64> {
+    Ok((level as u64)
+        .checked_mul(MLM_REWARD_PER_LEVEL)
+        .ok_or(MyContractError::MathOverflow)?)
+}
+
+/// Balances of `from` and `to` after moving `amount` lamports between them.
+pub fn transfer_balances(from: u64, to: u64, amount: u64) -> Result<(u64, u64)> {
+    let from = from
+        .checked_sub(amount)
+        .ok_or(MyContractError::MathUnderflow)?;
+    let to = to
+        .checked_add(amount)
+        .ok_or(MyContractError::MathOverflow)?;
+    Ok((from, to))
+}
@@ -1,30 +1,884 @@
 use anchor_lang::prelude::*;
 
+use crate::error::MyContractError;
+
+pub const ICO_SEED: &[u8] = b"ico";
+pub const USER_SEED: &[u8] = b"user";
+pub const PAYMENT_SEED: &[u8] = b"payment";
+pub const SALE_VAULT_SEED: &[u8] = b"sale_vault";
+pub const PAYMENT_VAULT_SEED: &[u8] = b"payment_vault";
+pub const MINT_AUTHORITY_SEED: &[u8] = b"mint_authority";
+pub const SUPPLY_AUTHORITY_SEED: &[u8] = b"supply_authority";
+pub const REWARDS_VAULT_SEED: &[u8] = b"rewards_vault";
+pub const DISTRIBUTION_SEED: &[u8] = b"distribution";
+pub const TEAM_SEED: &[u8] = b"team";
+pub const LIQUIDITY_SEED: &[u8] = b"liquidity";
+
+// Bits of `ICOState::pause_flags`, one per subsystem of this program. The
+// standalone vesting and treasury programs don't read them.
+pub const PAUSE_SALES: u8 = 1 << 0; // Purchases
+pub const PAUSE_CLAIMS: u8 = 1 << 1; // Refunds and batch settlement
+pub const PAUSE_TREASURY: u8 = 1 << 2; // Proceeds withdrawals
+pub const PAUSE_ALL: u8 = PAUSE_SALES | PAUSE_CLAIMS | PAUSE_TREASURY;
+
+pub const BASIS_POINTS: u16 = 10_000;
+pub const MAX_PHASES: usize = 10;
+pub const MAX_PAYMENT_MINTS: usize = 4;
+
 #[account]
+#[derive(Default, InitSpace)]
 pub struct ICOState {
     pub start_timestamp: i64,
     pub current_phase: u8,
+    pub phase_started_at: i64,
     pub total_tokens_sold: u64,
+    #[max_len(MAX_PHASES)]
     pub phase_details: Vec<PhaseDetail>,
     pub total_tokens_allocated: u64,
+    pub pledge_mint: Pubkey,
+    pub mint_authority_bump: u8,
+    pub bump: u8,
+    pub admin: Pubkey,
+    pub pending_admin: Option<Pubkey>,
+    pub treasury: Pubkey,
+    pub emergency_authority: Pubkey,
+    pub pause_flags: u8,
+    pub config: SaleConfig,
+    pub status: SaleStatus,
+    #[max_len(MAX_PAYMENT_MINTS)]
+    pub payment_mints: Vec<PaymentMint>,
+    pub proceeds_withdrawn: bool, // Once set the sale can no longer be cancelled
+    pub last_purchase_slot: u64,
+    pub slot_purchases: u32,  // Purchases made in `last_purchase_slot`
+    pub total_committed: u64, // Batch auctions: base units bid for
+    pub total_committed_lamports: u64, // Batch auctions: lamports deposited
+    pub lamports_withdrawn: u64,
+}
+
+impl ICOState {
+    pub fn is_paused(&self, subsystem: u8) -> bool {
+        self.pause_flags & subsystem != 0
+    }
+
+    /// Rejects phase schedules the sale engine can't run: empty or oversized,
+    /// out-of-order phase numbers, non-positive durations, empty or overlapping
+    /// windows, zero prices, a start that isn't in the future, prices that break
+    /// the price policy, or phase allocations that don't fit in
+    /// `total_tokens_allocated`.
+    pub fn validate_schedule(&self, now: i64) -> Result<()> {
+        require!(
+            !self.phase_details.is_empty(),
+            MyContractError::EmptyPhaseSchedule
+        );
+        require!(
+            self.phase_details.len() <= MAX_PHASES,
+            MyContractError::TooManyPhases
+        );
+        require!(
+            self.start_timestamp > now,
+            MyContractError::StartNotInFuture
+        );
+
+        let mut previous_end = self.start_timestamp;
+        for (i, phase) in self.phase_details.iter().enumerate() {
+            require!(
+                phase.phase as usize == i,
+                MyContractError::InvalidPhaseOrder
+            );
+            match phase.timing {
+                PhaseTiming::Relative { duration } => {
+                    require!(duration > 0, MyContractError::InvalidPhaseDuration)
+                }
+                PhaseTiming::Absolute { start, end } => require!(
+                    start >= previous_end && end > start,
+                    MyContractError::InvalidPhaseWindow
+                ),
+            }
+            previous_end = phase.window(previous_end)?.1;
+            require!(
+                phase.token_price.tokens > 0 && phase.token_price.lamports > 0,
+                MyContractError::InvalidPrice
+            );
+            if let PriceSchedule::DutchAuction { floor, .. } = phase.price_schedule {
+                require!(
+                    floor.tokens == phase.token_price.tokens
+                        && floor.lamports > 0
+                        && floor.lamports <= phase.token_price.lamports,
+                    MyContractError::InvalidPrice
+                );
+            }
+            require!(
+                phase.token_allocation > 0,
+                MyContractError::InvalidPhaseAllocation
+            );
+        }
+
+        let phase_allocations = self
+            .phase_details
+            .iter()
+            .try_fold(0u64, |sum, p| sum.checked_add(p.token_allocation))
+            .ok_or(MyContractError::MathOverflow)?;
+        require!(
+            phase_allocations <= self.total_tokens_allocated,
+            MyContractError::InvalidPhaseAllocation
+        );
+        // A soft cap above what can be sold would fail every sale
+        require!(
+            self.config.soft_cap <= self.total_tokens_allocated,
+            MyContractError::InvalidSoftCap
+        );
+
+        if let Some(curve) = &self.config.pricing_curve {
+            curve.validate()?;
+            // A curve prices by tokens already sold, which a batch auction only
+            // knows once bidding closes
+            require!(!self.config.batch_auction, MyContractError::InvalidPrice);
+        }
+
+        if let Some(oracle) = &self.config.oracle {
+            oracle.validate()?;
+        }
+
+        // Phase edits must not leave an accepted mint without a price
+        for payment_mint in &self.payment_mints {
+            payment_mint
+                .pricing
+                .validate(&self.phase_details, &self.config)?;
+        }
+
+        if self.config.price_policy == PricePolicy::NonDecreasing {
+            for pair in self.phase_details.windows(2) {
+                require!(
+                    !pair[1].token_price.is_cheaper_than(&pair[0].token_price),
+                    MyContractError::PhasePriceDecreased
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Lifecycle state at `now`. `Finalized` and `Cancelled` are terminal and
+    /// only ever set by an instruction; everything else follows the clock, except
+    /// that a sold out sale ends early. Gaps between absolute windows are `Pending`.
+    pub fn compute_status(&self, now: i64) -> Result<SaleStatus> {
+        if matches!(self.status, SaleStatus::Finalized | SaleStatus::Cancelled) {
+            return Ok(self.status);
+        }
+        if now < self.start_timestamp {
+            return Ok(SaleStatus::Pending);
+        }
+        if self.sold_out() {
+            return Ok(SaleStatus::Ended);
+        }
+
+        let mut previous_end = self.start_timestamp;
+        for (i, phase) in self.phase_details.iter().enumerate() {
+            let (start, end) = phase.window(previous_end)?;
+            if now < start {
+                return Ok(SaleStatus::Pending);
+            }
+            if now < end {
+                return Ok(SaleStatus::Active(i as u8));
+            }
+            previous_end = end;
+        }
+        Ok(SaleStatus::Ended)
+    }
+
+    /// Replaces the schedule, discarding any counters supplied by the caller.
+    pub fn set_phase_details(&mut self, mut phase_details: Vec<PhaseDetail>) {
+        for phase in phase_details.iter_mut() {
+            phase.tokens_sold = 0;
+            phase.purchases = 0;
+        }
+        self.phase_details = phase_details;
+    }
+
+    /// Price of every phase at `now`, following each phase's price schedule.
+    pub fn phase_prices(&self, now: i64) -> Result<Vec<TokenPrice>> {
+        let mut previous_end = self.start_timestamp;
+        self.phase_details
+            .iter()
+            .map(|phase| {
+                let (start, end) = phase.window(previous_end)?;
+                previous_end = end;
+                phase.price_at(start, end, now)
+            })
+            .collect()
+    }
+
+    /// Timestamp at which `phase` begins.
+    pub fn phase_start(&self, phase: u8) -> Result<i64> {
+        let mut previous_end = self.start_timestamp;
+        for p in self.phase_details.iter().take(phase as usize) {
+            previous_end = p.window(previous_end)?.1;
+        }
+        Ok(self.phase_details[phase as usize].window(previous_end)?.0)
+    }
+
+    pub fn record_phase_purchase(&mut self, phase: u8) -> Result<()> {
+        let phase = &mut self.phase_details[phase as usize];
+        phase.purchases = phase
+            .purchases
+            .checked_add(1)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn payment_mint_index(&self, mint: &Pubkey) -> Result<usize> {
+        self.payment_mints
+            .iter()
+            .position(|payment| payment.mint == *mint)
+            .ok_or(MyContractError::PaymentMintNotAccepted.into())
+    }
+
+    pub fn payment_pricing(&self, mint: &Pubkey) -> Result<&PaymentPricing> {
+        Ok(&self.payment_mints[self.payment_mint_index(mint)?].pricing)
+    }
+
+    /// Every phase allocation, and with it the hard cap, has been sold. Batch
+    /// auctions keep taking bids until the commit period ends.
+    pub fn sold_out(&self) -> bool {
+        !self.config.batch_auction
+            && (self.total_tokens_sold >= self.total_tokens_allocated
+                || self
+                    .phase_details
+                    .iter()
+                    .all(|phase| phase.tokens_remaining() == 0))
+    }
+
+    /// Holds a purchase of `tokens`, already recorded, to the minimum purchase.
+    /// The remainder that closes out a phase, `phase_remaining` base units before
+    /// the purchase, or the sale may be smaller than the minimum.
+    pub fn check_min_purchase(&self, tokens: u64, phase_remaining: u64) -> Result<()> {
+        let takes_remainder = !self.config.batch_auction && tokens == phase_remaining;
+        require!(
+            tokens >= self.config.min_purchase || takes_remainder || self.sold_out(),
+            MyContractError::BelowMinimumPurchase
+        );
+        Ok(())
+    }
+
+    pub fn soft_cap_reached(&self) -> bool {
+        self.total_tokens_sold >= self.config.soft_cap
+    }
+
+    /// Proceeds are safe to move to the treasury once no buyer can claim a refund.
+    pub fn can_withdraw_proceeds(&self) -> bool {
+        match self.status {
+            SaleStatus::Finalized => true,
+            SaleStatus::Cancelled => false,
+            // Oversubscription refunds aren't known until the commit period ends
+            _ => !self.config.batch_auction && self.soft_cap_reached(),
+        }
+    }
+
+    /// Counts lamports leaving the sale vault for the treasury. A batch auction
+    /// keeps back whatever its buyers will be refunded at settlement.
+    pub fn record_withdrawal(&mut self, lamports: u64) -> Result<()> {
+        let withdrawn = self
+            .lamports_withdrawn
+            .checked_add(lamports)
+            .ok_or(MyContractError::MathOverflow)?;
+        if self.config.batch_auction {
+            let (_, proceeds) =
+                self.batch_fill(self.total_committed, self.total_committed_lamports);
+            require!(
+                withdrawn <= proceeds,
+                MyContractError::WithdrawalExceedsProceeds
+            );
+        }
+        self.lamports_withdrawn = withdrawn;
+        Ok(())
+    }
+
+    /// Records a batch auction bid of `tokens` for `lamports`. The sold counter
+    /// tracks what will clear, so soft cap checks work during the commit period.
+    pub fn record_commitment(&mut self, tokens: u64, lamports: u64) -> Result<()> {
+        self.total_committed = self
+            .total_committed
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        self.total_committed_lamports = self
+            .total_committed_lamports
+            .checked_add(lamports)
+            .ok_or(MyContractError::MathOverflow)?;
+        self.total_tokens_sold = self.total_committed.min(self.total_tokens_allocated);
+        Ok(())
+    }
+
+    /// Share of a batch auction bid of `tokens` for `lamports` that clears: every
+    /// bid is filled in the same proportion when the auction is oversubscribed.
+    /// Returns the tokens allocated, rounded down, and the lamports kept for them,
+    /// rounded up so the kept amounts always cover what `record_withdrawal`
+    /// lets the treasury take; the rest of the deposit is refunded.
+    pub fn batch_fill(&self, tokens: u64, lamports: u64) -> (u64, u64) {
+        if self.total_committed <= self.total_tokens_allocated {
+            return (tokens, lamports);
+        }
+        let (allocated, committed) = (
+            self.total_tokens_allocated as u128,
+            self.total_committed as u128,
+        );
+        let filled = tokens as u128 * allocated / committed;
+        let kept = (lamports as u128 * allocated).div_ceil(committed);
+        (filled as u64, kept as u64)
+    }
+
+    /// Buyers can reclaim their payments once the sale is cancelled, or once it
+    /// ends without selling the soft cap.
+    pub fn refunds_open(&self, now: i64) -> Result<bool> {
+        Ok(match self.compute_status(now)? {
+            SaleStatus::Cancelled => true,
+            SaleStatus::Ended => !self.soft_cap_reached(),
+            _ => false,
+        })
+    }
+
+    /// Counts a purchase in `slot`, refusing more than `max_per_slot` (0 = unlimited).
+    pub fn record_slot_purchase(&mut self, slot: u64, max_per_slot: u32) -> Result<()> {
+        if self.last_purchase_slot == slot {
+            self.slot_purchases = self
+                .slot_purchases
+                .checked_add(1)
+                .ok_or(MyContractError::MathOverflow)?;
+        } else {
+            self.last_purchase_slot = slot;
+            self.slot_purchases = 1;
+        }
+        require!(
+            max_per_slot == 0 || self.slot_purchases <= max_per_slot,
+            MyContractError::SlotPurchaseLimitReached
+        );
+        Ok(())
+    }
+
+    /// Adds `tokens` to the sold counter, refusing to go past the allocation.
+    pub fn record_sale(&mut self, tokens: u64) -> Result<()> {
+        let total_tokens_sold = self
+            .total_tokens_sold
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        require!(
+            total_tokens_sold <= self.total_tokens_allocated,
+            MyContractError::OverPurchase
+        );
+        self.total_tokens_sold = total_tokens_sold;
+        Ok(())
+    }
 }
 
 #[account]
+#[derive(Default, InitSpace)]
 pub struct UserState {
     pub user: Pubkey,
     pub amount_purchased: u64,
     pub vesting_start_timestamp: i64,
     pub rewards_claimed: u64,
+    pub bump: u8,
+    pub phase_purchased: [u64; MAX_PHASES],
+    pub last_purchase_at: i64,
+    pub tokens_committed: u64, // Batch auctions: base units bid for, before pro-rata
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+impl UserState {
+    /// Adds a batch auction bid, returning the wallet's total bid.
+    pub fn record_commitment(&mut self, tokens: u64) -> Result<u64> {
+        self.tokens_committed = self
+            .tokens_committed
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(self.tokens_committed)
+    }
+
+    pub fn record_purchase(&mut self, tokens: u64) -> Result<()> {
+        self.amount_purchased = self
+            .amount_purchased
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Records a purchase at `now`, refusing it if the previous one was less than
+    /// `cooldown` seconds ago.
+    pub fn record_purchase_time(&mut self, now: i64, cooldown: i64) -> Result<()> {
+        if cooldown > 0 && self.last_purchase_at != 0 {
+            let next_allowed = self
+                .last_purchase_at
+                .checked_add(cooldown)
+                .ok_or(MyContractError::MathOverflow)?;
+            require!(now >= next_allowed, MyContractError::PurchaseCooldown);
+        }
+        self.last_purchase_at = now;
+        Ok(())
+    }
+
+    /// Counts `tokens` against the wallet's limit for the phase they were bought in.
+    pub fn record_phase_purchase(&mut self, phase: u8, tokens: u64) -> Result<()> {
+        let purchased = &mut self.phase_purchased[phase as usize];
+        *purchased = purchased
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// What one payer paid towards one beneficiary's purchases. Each payer gets its
+/// own record, so refunds go back to whoever paid no matter who holds the tokens.
+#[account]
+#[derive(Default, InitSpace)]
+pub struct PaymentRecord {
+    pub beneficiary: Pubkey,
+    pub payer: Pubkey,
+    pub bump: u8,
+    pub sol_paid: u64,                        // Lamports paid into the sale vault
+    pub token_paid: [u64; MAX_PAYMENT_MINTS], // Indexed like `ICOState::payment_mints`
+    pub tokens_minted: u64,                   // Pledge base units minted to the beneficiary
+    pub tokens_committed: u64,                // Batch auctions: base units bid for, before pro-rata
+    pub settled: bool,
+}
+
+impl PaymentRecord {
+    /// Records `amount` paid for `tokens` base units, minted right away or only
+    /// bid for in a batch auction. `payment_mint` is an index into
+    /// `ICOState::payment_mints`, `None` for SOL.
+    pub fn record_payment(
+        &mut self,
+        payment_mint: Option<usize>,
+        amount: u64,
+        tokens: u64,
+        batch_auction: bool,
+    ) -> Result<()> {
+        let paid = match payment_mint {
+            Some(index) => &mut self.token_paid[index],
+            None => &mut self.sol_paid,
+        };
+        *paid = paid
+            .checked_add(amount)
+            .ok_or(MyContractError::MathOverflow)?;
+        let counter = if batch_auction {
+            &mut self.tokens_committed
+        } else {
+            &mut self.tokens_minted
+        };
+        *counter = counter
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Clears the record for a refund of a failed sale. Returns the Pledge base
+    /// units to burn and the lamports and base units of `payment_mint` owed to
+    /// the payer; other payment mints are refunded by later calls.
+    pub fn take_refund(&mut self, payment_mint: Option<usize>) -> Result<(u64, u64, u64)> {
+        let burn = self.tokens_minted;
+        let sol_refund = self.sol_paid;
+        let token_refund = match payment_mint {
+            Some(index) => std::mem::take(&mut self.token_paid[index]),
+            None => 0,
+        };
+        require!(
+            burn > 0 || sol_refund > 0 || token_refund > 0,
+            MyContractError::NothingToRefund
+        );
+        self.tokens_minted = 0;
+        self.sol_paid = 0;
+        Ok((burn, sol_refund, token_refund))
+    }
+
+    /// Settles the record's batch auction bids, returning the base units to mint
+    /// and the lamports that didn't clear, owed back to the payer.
+    pub fn settle(&mut self, ico_state: &ICOState) -> Result<(u64, u64)> {
+        require!(!self.settled, MyContractError::AlreadySettled);
+        let (tokens, kept) = ico_state.batch_fill(self.tokens_committed, self.sol_paid);
+        let refund = self
+            .sol_paid
+            .checked_sub(kept)
+            .ok_or(MyContractError::MathUnderflow)?;
+        self.settled = true;
+        self.sol_paid = kept;
+        self.tokens_minted = self
+            .tokens_minted
+            .checked_add(tokens)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok((tokens, refund))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
 pub struct PhaseDetail {
     pub phase: u8,
-    pub duration: i64,
-    pub token_price: u64,
+    pub timing: PhaseTiming,
+    pub token_price: TokenPrice,
+    pub token_allocation: u64,
+    pub tokens_sold: u64,
+    pub purchases: u32,
+    pub max_per_wallet: u64,              // 0 = no per-phase wallet limit
+    pub allowlist_root: Option<[u8; 32]>, // Merkle root of eligible wallets, None = public
+    pub bot_protection: BotProtection,
+    pub price_schedule: PriceSchedule,
+}
+
+/// Optional anti-bot limits of a phase; the default turns them all off.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, InitSpace)]
+pub struct BotProtection {
+    pub wallet_cooldown: i64, // Seconds between purchases by one wallet, 0 = none
+    pub max_purchases_per_slot: u32, // Across all wallets, 0 = unlimited
+    pub verifier: Option<Pubkey>, // Key that must co-sign every purchase, see `attestation`
+}
+
+impl PhaseDetail {
+    pub fn tokens_remaining(&self) -> u64 {
+        self.token_allocation.saturating_sub(self.tokens_sold)
+    }
+
+    /// Whether a buyer cleared for this phase is also cleared for `other`: both
+    /// use the same allowlist and the same purchase verifier.
+    pub fn admits_same_buyers(&self, other: &PhaseDetail) -> bool {
+        self.allowlist_root == other.allowlist_root
+            && self.bot_protection.verifier == other.bot_protection.verifier
+    }
+
+    /// Price at `now` of this phase running over `[start, end)`. Before the phase
+    /// it is the start price, after it the floor.
+    pub fn price_at(&self, start: i64, end: i64, now: i64) -> Result<TokenPrice> {
+        let (floor, steps) = match self.price_schedule {
+            PriceSchedule::Fixed => return Ok(self.token_price),
+            PriceSchedule::DutchAuction { floor, steps } => (floor, steps as u128),
+        };
+
+        let duration = end
+            .checked_sub(start)
+            .ok_or(MyContractError::MathUnderflow)? as u128;
+        let elapsed = now
+            .clamp(start, end)
+            .checked_sub(start)
+            .ok_or(MyContractError::MathUnderflow)? as u128;
+        let (done, total) = if steps == 0 {
+            (elapsed, duration)
+        } else {
+            ((elapsed * (steps + 1) / duration).min(steps), steps)
+        };
+
+        let range = self
+            .token_price
+            .lamports
+            .checked_sub(floor.lamports)
+            .ok_or(MyContractError::MathUnderflow)?;
+        let decay = (range as u128 * done / total) as u64; // Rounded down, in favour of the sale
+        Ok(TokenPrice {
+            tokens: self.token_price.tokens,
+            lamports: self.token_price.lamports - decay,
+        })
+    }
+
+    /// `[start, end)` of this phase when the phase before it ends at `previous_end`.
+    pub fn window(&self, previous_end: i64) -> Result<(i64, i64)> {
+        match self.timing {
+            PhaseTiming::Relative { duration } => Ok((
+                previous_end,
+                previous_end
+                    .checked_add(duration)
+                    .ok_or(MyContractError::MathOverflow)?,
+            )),
+            PhaseTiming::Absolute { start, end } => Ok((start, end)),
+        }
+    }
+}
+
+/// How a phase's price moves while it runs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub enum PriceSchedule {
+    /// `token_price` for the whole phase.
+    #[default]
+    Fixed,
+    /// Starts at `token_price` and falls to `floor`, which must be quoted for the
+    /// same number of tokens. `steps == 0` decays linearly until the phase ends;
+    /// otherwise the phase is cut into `steps + 1` equal intervals, the price
+    /// dropping once per interval and sitting at `floor` for the last one.
+    DutchAuction { floor: TokenPrice, steps: u32 },
+}
+
+/// When a phase runs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PhaseTiming {
+    /// `duration` seconds from the end of the previous phase, or from `start_timestamp`.
+    Relative { duration: i64 },
+    /// A fixed window of unix timestamps, `end` exclusive.
+    Absolute { start: i64, end: i64 },
+}
+
+/// Exchange rate of a phase: `tokens` Pledge base units (9 decimals) for every
+/// `lamports` lamports paid.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct TokenPrice {
+    pub tokens: u64,
+    pub lamports: u64,
+}
+
+impl TokenPrice {
+    /// True when this price gives more tokens per lamport than `other`.
+    pub fn is_cheaper_than(&self, other: &TokenPrice) -> bool {
+        self.tokens as u128 * other.lamports as u128 > other.tokens as u128 * self.lamports as u128
+    }
+}
+
+/// An SPL token the sale accepts as payment, e.g. USDC or USDT.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PaymentMint {
+    pub mint: Pubkey,
+    pub pricing: PaymentPricing,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub enum PaymentPricing {
+    /// `prices[i]` is phase `i`'s price, with `lamports` read as base units of the mint.
+    PerPhase {
+        #[max_len(MAX_PHASES)]
+        prices: Vec<TokenPrice>,
+    },
+    /// Converted at a fixed rate into the currency phase prices are quoted in,
+    /// e.g. 1 USDC for 1 USD, then priced like any other payment.
+    Converted(ConversionRate),
+}
+
+impl PaymentPricing {
+    /// Rejects rates and price lists that could not price every phase. Fixed
+    /// per-phase prices would skip a Dutch auction's decay or a bonding curve,
+    /// so those sales only take converted mints. Batch auctions are settled in
+    /// SOL and take no other mint.
+    pub fn validate(&self, phases: &[PhaseDetail], config: &SaleConfig) -> Result<()> {
+        require!(!config.batch_auction, MyContractError::BatchAuctionSolOnly);
+        match self {
+            PaymentPricing::PerPhase { prices } => require!(
+                prices.len() == phases.len()
+                    && prices.iter().all(|p| p.tokens > 0 && p.lamports > 0)
+                    && phases
+                        .iter()
+                        .all(|p| matches!(p.price_schedule, PriceSchedule::Fixed))
+                    && config.pricing_curve.is_none(),
+                MyContractError::InvalidPrice
+            ),
+            PaymentPricing::Converted(rate) => require!(
+                rate.payment_units > 0 && rate.quote_units > 0,
+                MyContractError::InvalidPrice
+            ),
+        }
+        Ok(())
+    }
+
+    /// Price of phase `index` in this mint, given the phase's own price.
+    pub fn price(&self, index: usize, phase_price: &TokenPrice) -> Result<TokenPrice> {
+        match self {
+            PaymentPricing::PerPhase { prices } => prices
+                .get(index)
+                .copied()
+                .ok_or(MyContractError::InvalidPrice.into()),
+            PaymentPricing::Converted(rate) => {
+                let tokens = phase_price.tokens as u128 * rate.quote_units as u128;
+                let units = phase_price.lamports as u128 * rate.payment_units as u128;
+                let divisor = gcd(tokens, units).max(1);
+                Ok(TokenPrice {
+                    tokens: u64::try_from(tokens / divisor)
+                        .map_err(|_| MyContractError::MathOverflow)?,
+                    lamports: u64::try_from(units / divisor)
+                        .map_err(|_| MyContractError::MathOverflow)?,
+                })
+            }
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// `payment_units` base units of a payment mint are worth `quote_units` of the
+/// currency phase prices are quoted in.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, InitSpace)]
+pub struct ConversionRate {
+    pub payment_units: u64,
+    pub quote_units: u64,
+}
+
+/// How fractional base units are rounded when converting a payment to tokens.
+/// Rounding up never hands out unpaid units: the purchase fails unless the
+/// payment also covers the extra unit.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum RoundingPolicy {
+    #[default]
+    Down,
+    Nearest,
+    Up,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct RewardEntitlement {
     pub amount: u64,
 }
+
+/// How the fixed Solhit supply is split at genesis, in basis points of `total_supply`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SupplyAllocation {
+    pub total_supply: u64,
+    pub rewards_bps: u16,
+    pub distribution_bps: u16,
+    pub team_bps: u16,
+    pub liquidity_bps: u16,
+}
+
+impl SupplyAllocation {
+    /// Returns the rewards, distribution, team and liquidity amounts, in that order.
+    /// Rounding dust goes to the distribution bucket so the buckets always add up
+    /// to `total_supply` exactly.
+    pub fn split(&self) -> Result<[u64; 4]> {
+        let bps = [
+            self.rewards_bps,
+            self.distribution_bps,
+            self.team_bps,
+            self.liquidity_bps,
+        ];
+        let bps_sum = bps
+            .iter()
+            .try_fold(0u16, |sum, b| sum.checked_add(*b))
+            .ok_or(MyContractError::InvalidSupplyAllocation)?;
+        require!(
+            self.total_supply > 0 && bps_sum == BASIS_POINTS,
+            MyContractError::InvalidSupplyAllocation
+        );
+
+        let mut amounts = [0u64; 4];
+        for (amount, share) in amounts.iter_mut().zip(bps) {
+            let scaled = (self.total_supply as u128)
+                .checked_mul(share as u128)
+                .ok_or(MyContractError::MathOverflow)?
+                / BASIS_POINTS as u128;
+            *amount = u64::try_from(scaled).map_err(|_| MyContractError::MathOverflow)?;
+        }
+        let minted = amounts
+            .iter()
+            .try_fold(0u64, |sum, a| sum.checked_add(*a))
+            .ok_or(MyContractError::MathOverflow)?;
+        let dust = self
+            .total_supply
+            .checked_sub(minted)
+            .ok_or(MyContractError::MathUnderflow)?;
+        amounts[1] = amounts[1]
+            .checked_add(dust)
+            .ok_or(MyContractError::MathOverflow)?;
+        Ok(amounts)
+    }
+}
+
+/// Sale-wide policies chosen when the ICO is started.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct SaleConfig {
+    pub rounding: RoundingPolicy,
+    pub price_policy: PricePolicy,
+    pub phase_overflow: PhaseOverflow,
+    pub min_purchase: u64,   // Smallest purchase in base units
+    pub max_per_wallet: u64, // Lifetime base units per wallet, 0 = unlimited
+    pub soft_cap: u64,       // Base units that must sell for the sale to succeed, 0 = none
+    /// When set, phase prices are quoted in micro-USD and SOL is converted through this feed.
+    pub oracle: Option<OracleConfig>,
+    /// When set, prices follow this curve instead of the phase prices.
+    pub pricing_curve: Option<PricingCurve>,
+    /// Purchases only commit SOL while the phases run; once the sale ends every
+    /// buyer settles for a pro-rata share and a refund of the oversubscription.
+    pub batch_auction: bool,
+}
+
+/// Bonding curve pricing a sale by `total_tokens_sold`. Prices are in lamports
+/// (or the oracle's quote currency) per whole Pledge token.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PricingCurve {
+    /// `start_price`, plus `slope` for every whole token already sold.
+    Linear { start_price: u64, slope: u64 },
+    /// `start_price`, compounded by `growth_bps` after every `step` base units sold.
+    Exponential {
+        start_price: u64,
+        growth_bps: u16,
+        step: u64,
+    },
+}
+
+impl PricingCurve {
+    pub fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            PricingCurve::Linear { start_price, .. } => start_price > 0,
+            PricingCurve::Exponential {
+                start_price,
+                growth_bps,
+                step,
+            } => start_price > 0 && growth_bps > 0 && step > 0,
+        };
+        require!(valid, MyContractError::InvalidPrice);
+        Ok(())
+    }
+}
+
+/// SOL/USD price feed used to convert SOL payments before tier pricing.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct OracleConfig {
+    pub feed: Pubkey,            // Pyth SOL/USD price account
+    pub max_age: i64,            // Seconds since the price was published
+    pub max_confidence_bps: u16, // Widest acceptable confidence interval, relative to the price
+}
+
+impl OracleConfig {
+    pub fn validate(&self) -> Result<()> {
+        // A zero or negative age would turn away practically every price
+        require!(self.max_age > 0, MyContractError::InvalidOracleConfig);
+        Ok(())
+    }
+}
+
+/// What happens to the part of a purchase that doesn't fit in the phase's allocation.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum PhaseOverflow {
+    /// The whole purchase fails.
+    #[default]
+    Reject,
+    /// The buyer gets whatever is left of the phase and pays only for that.
+    PartialFill,
+    /// The rest is bought from the following phases at their prices.
+    SpillToNext,
+}
+
+/// Constraint on how prices may change from one phase to the next.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum PricePolicy {
+    /// Every phase costs at least as much as the one before it.
+    #[default]
+    NonDecreasing,
+    /// Phases may be priced freely.
+    Any,
+}
+
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum SaleStatus {
+    /// Before `start_timestamp`, or between two absolute phase windows.
+    #[default]
+    Pending,
+    /// Inside the given phase.
+    Active(u8),
+    /// Every phase has elapsed but the sale hasn't been finalized yet.
+    Ended,
+    Finalized,
+    Cancelled,
+}
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::system_program;
+use crate::error::MyContractError;
+use crate::rewards;
 
 declare_id!("...");
 
@@ -34,8 +36,11 @@ pub mod treasury {
 
     pub fn claim_funds(ctx: Context<ClaimFunds>) -> Result<()> {
         let beneficiary_info = ctx.accounts.treasury_state.beneficiaries.iter().find(|x| x.beneficiary == *ctx.accounts.beneficiary.key()).ok_or(ErrorCode::Unauthorized)?;
-        **ctx.accounts.treasury.to_account_info().try_borrow_mut_lamports()? -= beneficiary_info.amount;
-        **ctx.accounts.beneficiary.to_account_info().try_borrow_mut_lamports()? += beneficiary_info.amount;
+        let treasury_info = ctx.accounts.treasury.to_account_info();
+        let beneficiary_account = ctx.accounts.beneficiary.to_account_info();
+        let (treasury_lamports, beneficiary_lamports) = rewards::transfer_balances(treasury_info.lamports(), beneficiary_account.lamports(), beneficiary_info.amount)?;
+        **treasury_info.try_borrow_mut_lamports()? = treasury_lamports;
+        **beneficiary_account.try_borrow_mut_lamports()? = beneficiary_lamports;
         Ok(())
     }
 }
@@ -1,7 +1,9 @@
 use anchor_lang::prelude::*;
 use crate::state::{UserState, RewardEntitlement};
+use crate::error::MyContractError;
 use anchor_spl::token::{self, Transfer, TokenAccount, Token};
 use crate::mlm::MLMStrategy; 
+use crate::rewards;
 
 declare_id!("...");
 
@@ -17,7 +19,7 @@ pub mod solsticetoken_vesting {
         vesting_account.amount_purchased = amount;
         vesting_account.vesting_start_timestamp = Clock::get()?.unix_timestamp;
         // Set vesting end timestamp to 2 years from the start
-        vesting_account.vesting_end_timestamp = vesting_account.vesting_start_timestamp + (2 * 365 * 24 * 60 * 60);
+        vesting_account.vesting_end_timestamp = rewards::vesting_end(vesting_account.vesting_start_timestamp)?;
         vesting_account.rewards_claimed = 0;
 
         // Initialize MLM participation
@@ -34,19 +36,18 @@ pub mod solsticetoken_vesting {
             return Err(ErrorCode::VestingPeriodNotCompleted.into());
         }
 
-        let total_rewards = vesting_account.amount_purchased * 40; // Example reward calculation
-        let rewards_to_claim = total_rewards - vesting_account.rewards_claimed;
+        let rewards_to_claim = rewards::rewards_due(vesting_account.amount_purchased, vesting_account.rewards_claimed)?;
 
         // Ensure rewards have not already been claimed
-        if rewards_to_claim <= 0 {
+        if rewards_to_claim == 0 {
             return Err(ErrorCode::NoRewardsAvailable.into());
         }
 
-        vesting_account.rewards_claimed += rewards_to_claim;
+        vesting_account.rewards_claimed = vesting_account.rewards_claimed.checked_add(rewards_to_claim).ok_or(MyContractError::MathOverflow)?;
 
         // Additional logic to calculate MLM rewards
         let mlm_rewards = MLMStrategy::calculate_rewards(ctx.accounts.mlm_participant.to_account_info())?;
-        let total_rewards = rewards_to_claim + mlm_rewards;
+        let total_rewards = rewards_to_claim.checked_add(mlm_rewards).ok_or(MyContractError::MathOverflow)?;
 
         // Transfer total rewards (vesting + MLM) to the user
         let cpi_accounts = Transfer {
@@ -67,7 +68,7 @@ pub mod solsticetoken_vesting {
 
         
         if user_state.referred_by == Some(referred_user) {
-            user_state.rewards_claimed += referral_reward;
+            user_state.rewards_claimed = user_state.rewards_claimed.checked_add(referral_reward).ok_or(MyContractError::MathOverflow)?;
         } else {
            
             return Err(ErrorCode::InvalidReferral.into());
@@ -81,19 +82,17 @@ pub mod solsticetoken_vesting {
         let now = Clock::get()?.unix_timestamp;
 
         // Calculate the time elapsed since the last reward calculation
-        let time_elapsed = now - vesting_account.last_reward_calculation_timestamp;
-
         // Calculate existing rewards (this is a placeholder, implement your own logic)
-        let existing_rewards = (vesting_account.amount_purchased * time_elapsed as u64) / (365 * 24 * 60 * 60); // Example calculation
+        let existing_rewards = rewards::accrued_rewards(vesting_account.amount_purchased, vesting_account.last_reward_calculation_timestamp, now)?;
 
         // Update the locked amount
-        vesting_account.amount_purchased += additional_amount;
+        vesting_account.amount_purchased = vesting_account.amount_purchased.checked_add(additional_amount).ok_or(MyContractError::MathOverflow)?;
 
         // Reset the reward calculation mechanism
         vesting_account.last_reward_calculation_timestamp = now;
 
         // Optionally, update the rewards_claimed to include the newly calculated rewards
-        vesting_account.rewards_claimed += existing_rewards;
+        vesting_account.rewards_claimed = vesting_account.rewards_claimed.checked_add(existing_rewards).ok_or(MyContractError::MathOverflow)?;
 
         Ok(())
     }
@@ -102,7 +101,7 @@ pub mod solsticetoken_vesting {
         let vesting_account = &mut ctx.accounts.vesting_account;
         let now = Clock::get()?.unix_timestamp;
 
-        let time_elapsed = now - vesting_account.last_reward_calculation_timestamp.max(vesting_account.vesting_start_timestamp);
+        let time_elapsed = now.checked_sub(vesting_account.last_reward_calculation_timestamp.max(vesting_account.vesting_start_timestamp)).ok_or(MyContractError::MathUnderflow)?;
 
         let annual_interest_rate = 0.05; 
 
@@ -110,9 +109,9 @@ pub mod solsticetoken_vesting {
 
         let existing_rewards = (vesting_account.amount_purchased as f64 * (1.0 + annual_interest_rate).powf(years_elapsed)) - vesting_account.amount_purchased as f64;
 
-        vesting_account.amount_purchased += additional_amount;
+        vesting_account.amount_purchased = vesting_account.amount_purchased.checked_add(additional_amount).ok_or(MyContractError::MathOverflow)?;
 
-        vesting_account.rewards_claimed += existing_rewards as u64;
+        vesting_account.rewards_claimed = vesting_account.rewards_claimed.checked_add(existing_rewards as u64).ok_or(MyContractError::MathOverflow)?;
 
         // Reset the reward calculation mechanism
         vesting_account.last_reward_calculation_timestamp = now;
@@ -125,7 +124,7 @@ pub mod solsticetoken_vesting {
         let now = Clock::get()?.unix_timestamp;
 
         // Calculate the time elapsed since the last reward calculation or since the tokens were locked
-        let time_elapsed = now - vesting_account.last_reward_calculation_timestamp.max(vesting_account.vesting_start_timestamp);
+        let time_elapsed = now.checked_sub(vesting_account.last_reward_calculation_timestamp.max(vesting_account.vesting_start_timestamp)).ok_or(MyContractError::MathUnderflow)?;
 
         let annual_interest_rate = 0.05; // Clearly defined reward calculation mechanism
 
@@ -143,7 +142,7 @@ pub mod solsticetoken_vesting {
     fn determine_sale_level(now: i64) -> u8 {
         let sale_start_timestamp = /* timestamp for the start of the sale */;
         let level_duration = 15 * 24 * 60 * 60; // 15 days in seconds
-        let elapsed_time = now - sale_start_timestamp;
+        let elapsed_time = now.saturating_sub(sale_start_timestamp);
         let current_level = elapsed_time / level_duration;
 
         match current_level {
@@ -156,31 +155,24 @@ pub mod solsticetoken_vesting {
     }
 
     // Adjust the calculate_pledge_tokens function to calculate the number of Pledge tokens based on the USD amount and sale level
-    fn calculate_pledge_tokens(amount_usd: u64, sale_level: u8) -> u64 {
-        match sale_level {
-            1 => amount_usd * 2,
-            2 => amount_usd * 175 / 100,
-            3 => amount_usd * 15 / 10,
-            4 => amount_usd * 125 / 100,
-            5 => amount_usd, 
-            _ => 0, 
-        }
+    fn calculate_pledge_tokens(amount_usd: u64, sale_level: u8) -> Result<u64> {
+        rewards::pledge_tokens_for(amount_usd, sale_level)
     }
 
     pub fn purchase_and_vest(ctx: Context<PurchaseAndVest>, amount_usd: u64) -> Result<()> {
         let now = Clock::get()?.unix_timestamp;
         let sale_level = determine_sale_level(now);
-        let pledge_tokens = calculate_pledge_tokens(amount_usd, sale_level);
+        let pledge_tokens = calculate_pledge_tokens(amount_usd, sale_level)?;
     
         mint_pledge_tokens(ctx.accounts.pledge_token_mint.to_account_info(), ctx.accounts.user_pledge_token_account.to_account_info(), pledge_tokens)?;
     
         // Initialize vesting
         let vesting_account = &mut ctx.accounts.vesting_account;
         vesting_account.user = *ctx.accounts.user.key;
-        vesting_account.amount_purchased += pledge_tokens;
+        vesting_account.amount_purchased = vesting_account.amount_purchased.checked_add(pledge_tokens).ok_or(MyContractError::MathOverflow)?;
         vesting_account.vesting_start_timestamp = now;
         // Set vesting end timestamp to 2 years from the start
-        vesting_account.vesting_end_timestamp = now + (2 * 365 * 24 * 60 * 60);
+        vesting_account.vesting_end_timestamp = rewards::vesting_end(now)?;
         vesting_account.rewards_claimed = 0;
     
         // Initialize MLM participation
@@ -199,7 +191,7 @@ pub mod solsticetoken_vesting {
     fn determine_sale_level(now: i64) -> u8 {
         let sale_start_timestamp = /* timestamp for the start of the sale */;
         let level_duration = 15 * 24 * 60 * 60; // 15 days in seconds
-        let elapsed_time = now - sale_start_timestamp;
+        let elapsed_time = now.saturating_sub(sale_start_timestamp);
         let current_level = elapsed_time / level_duration;
 
         match current_level {
@@ -211,15 +203,8 @@ pub mod solsticetoken_vesting {
         }
     }
 
-    fn calculate_pledge_tokens(amount_usd: u64, sale_level: u8) -> u64 {
-        match sale_level {
-            1 => amount_usd * 2,
-            2 => amount_usd * 175 / 100,
-            3 => amount_usd * 15 / 10,
-            4 => amount_usd * 125 / 100,
-            5 => amount_usd, // Level 5 offers 1 Pledge token per $1
-            _ => 0, // Default case if the sale level is not recognized
-        }
+    fn calculate_pledge_tokens(amount_usd: u64, sale_level: u8) -> Result<u64> {
+        rewards::pledge_tokens_for(amount_usd, sale_level)
     }
 }
 
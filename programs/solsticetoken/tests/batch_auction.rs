@@ -0,0 +1,115 @@
+use proptest::prelude::*;
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{ICOState, SaleConfig};
+
+fn auction(allocated: u64) -> ICOState {
+    ICOState {
+        total_tokens_allocated: allocated,
+        config: SaleConfig {
+            batch_auction: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn bids() -> impl Strategy<Value = Vec<(u64, u64)>> {
+    prop::collection::vec((1..u64::MAX / 64, 0..u64::MAX / 64), 1..32)
+}
+
+proptest! {
+    #[test]
+    fn record_commitment_never_wraps(
+        committed in any::<u64>(),
+        committed_lamports in any::<u64>(),
+        allocated in any::<u64>(),
+        tokens in any::<u64>(),
+        lamports in any::<u64>(),
+    ) {
+        let mut ico_state = ICOState {
+            total_committed: committed,
+            total_committed_lamports: committed_lamports,
+            ..auction(allocated)
+        };
+        let result = ico_state.record_commitment(tokens, lamports);
+        match (committed.checked_add(tokens), committed_lamports.checked_add(lamports)) {
+            (Some(total), Some(total_lamports)) => {
+                prop_assert!(result.is_ok());
+                prop_assert_eq!(ico_state.total_committed, total);
+                prop_assert_eq!(ico_state.total_committed_lamports, total_lamports);
+                prop_assert_eq!(ico_state.total_tokens_sold, total.min(allocated));
+            }
+            _ => prop_assert_eq!(result.unwrap_err(), MyContractError::MathOverflow.into()),
+        }
+    }
+
+    #[test]
+    fn batch_fill_never_overallocates_or_overcharges(
+        allocated in 1..u64::MAX / 2,
+        bids in bids(),
+    ) {
+        let mut ico_state = auction(allocated);
+        for (tokens, lamports) in &bids {
+            ico_state.record_commitment(*tokens, *lamports).unwrap();
+        }
+        let oversubscribed = ico_state.total_committed > allocated;
+
+        let mut filled = 0u128;
+        for (tokens, lamports) in &bids {
+            let (tokens_filled, kept) = ico_state.batch_fill(*tokens, *lamports);
+            prop_assert!(tokens_filled <= *tokens);
+            prop_assert!(kept <= *lamports);
+            if !oversubscribed {
+                prop_assert_eq!((tokens_filled, kept), (*tokens, *lamports));
+            }
+            filled += tokens_filled as u128;
+        }
+        prop_assert!(filled <= allocated as u128);
+    }
+
+    #[test]
+    fn withdrawals_leave_every_refund_covered(
+        allocated in 1..u64::MAX / 2,
+        bids in bids(),
+        withdrawals in prop::collection::vec(0..u64::MAX / 64, 1..8),
+    ) {
+        let mut ico_state = auction(allocated);
+        for (tokens, lamports) in &bids {
+            ico_state.record_commitment(*tokens, *lamports).unwrap();
+        }
+        let (_, proceeds) =
+            ico_state.batch_fill(ico_state.total_committed, ico_state.total_committed_lamports);
+
+        for amount in withdrawals {
+            let before = ico_state.lamports_withdrawn;
+            match ico_state.record_withdrawal(amount) {
+                Ok(()) => prop_assert_eq!(ico_state.lamports_withdrawn, before + amount),
+                Err(err) => {
+                    prop_assert!(
+                        err == MyContractError::WithdrawalExceedsProceeds.into()
+                            || err == MyContractError::MathOverflow.into()
+                    );
+                    prop_assert!(before.checked_add(amount).is_none_or(|w| w > proceeds));
+                    prop_assert_eq!(ico_state.lamports_withdrawn, before);
+                }
+            }
+        }
+        // The treasury can always take the rest of the proceeds, and no more
+        let rest = proceeds - ico_state.lamports_withdrawn;
+        prop_assert!(ico_state.record_withdrawal(rest).is_ok());
+        prop_assert_eq!(
+            ico_state.record_withdrawal(1).unwrap_err(),
+            MyContractError::WithdrawalExceedsProceeds.into()
+        );
+
+        // Whatever the treasury took, the vault still holds every settlement refund
+        let refunds = bids
+            .iter()
+            .map(|(tokens, lamports)| (lamports - ico_state.batch_fill(*tokens, *lamports).1) as u128)
+            .sum::<u128>();
+        prop_assert!(
+            refunds + ico_state.lamports_withdrawn as u128
+                <= ico_state.total_committed_lamports as u128
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use proptest::prelude::*;
+use solsticetoken::pricing;
+use solsticetoken::state::PricingCurve;
+
+const TOKEN: u64 = 1_000_000_000;
+
+fn curve() -> impl Strategy<Value = PricingCurve> {
+    prop_oneof![
+        (1..1_000_000_000u64, 0..1_000_000u64)
+            .prop_map(|(start_price, slope)| PricingCurve::Linear { start_price, slope }),
+        (1..1_000_000_000u64, 1..5_000u16, 1..1_000_000u64).prop_map(
+            |(start_price, growth_bps, step)| PricingCurve::Exponential {
+                start_price,
+                growth_bps,
+                step: step * TOKEN,
+            }
+        ),
+    ]
+}
+
+#[test]
+fn linear_curve_charges_the_average_price() {
+    // 1 SOL for the first token, 1 SOL more for every token sold: ten tokens
+    // average 5.5 SOL
+    let curve = PricingCurve::Linear {
+        start_price: TOKEN,
+        slope: TOKEN,
+    };
+    assert_eq!(
+        pricing::curve_cost(&curve, 0, 10 * TOKEN).unwrap(),
+        60 * TOKEN
+    );
+    assert_eq!(
+        pricing::curve_cost(&curve, 10 * TOKEN, 10 * TOKEN).unwrap(),
+        160 * TOKEN
+    );
+}
+
+#[test]
+fn exponential_curve_compounds_per_step() {
+    // Doubles every 10 tokens: 5 at 1 SOL, 10 at 2, 10 at 4 and 5 at 8
+    let curve = PricingCurve::Exponential {
+        start_price: TOKEN,
+        growth_bps: 10_000,
+        step: 10 * TOKEN,
+    };
+    assert_eq!(
+        pricing::curve_cost(&curve, 0, 10 * TOKEN).unwrap(),
+        10 * TOKEN
+    );
+    assert_eq!(
+        pricing::curve_cost(&curve, 5 * TOKEN, 30 * TOKEN).unwrap(),
+        105 * TOKEN
+    );
+}
+
+proptest! {
+    #[test]
+    fn curve_cost_grows_with_quantity(
+        curve in curve(),
+        sold in 0..1_000_000 * TOKEN,
+        tokens in 0..1_000_000 * TOKEN,
+        extra in 1..1_000 * TOKEN,
+    ) {
+        if let (Ok(cost), Ok(more)) = (
+            pricing::curve_cost(&curve, sold, tokens),
+            pricing::curve_cost(&curve, sold, tokens + extra),
+        ) {
+            prop_assert!(more >= cost);
+        }
+    }
+
+    #[test]
+    fn splitting_a_purchase_never_saves_more_than_rounding(
+        curve in curve(),
+        sold in 0..1_000_000 * TOKEN,
+        first in 1..100_000 * TOKEN,
+        second in 1..100_000 * TOKEN,
+    ) {
+        if let (Ok(whole), Ok(a), Ok(b)) = (
+            pricing::curve_cost(&curve, sold, first + second),
+            pricing::curve_cost(&curve, sold, first),
+            pricing::curve_cost(&curve, sold + first, second),
+        ) {
+            prop_assert!(a + b + 4 >= whole);
+        }
+    }
+
+    #[test]
+    fn curve_tokens_for_is_the_most_the_budget_buys(
+        curve in curve(),
+        sold in 0..1_000_000 * TOKEN,
+        budget in 0..1_000_000 * TOKEN,
+    ) {
+        let tokens = pricing::curve_tokens_for(&curve, sold, budget, u64::MAX / 4).unwrap();
+        if tokens > 0 {
+            prop_assert!(pricing::curve_cost(&curve, sold, tokens).unwrap() <= budget);
+        }
+        if let Ok(cost) = pricing::curve_cost(&curve, sold, tokens + 1) {
+            prop_assert!(cost > budget);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use solsticetoken::attestation::{self, ATTESTATION_LEN};
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{ICOState, UserState};
+
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+#[test]
+fn cooldown_spaces_out_a_wallets_purchases() {
+    let mut user_state = UserState::default();
+    // The first purchase is never held back
+    assert!(user_state.record_purchase_time(1_000, 60).is_ok());
+    assert_eq!(
+        user_state.record_purchase_time(1_059, 60).unwrap_err(),
+        MyContractError::PurchaseCooldown.into()
+    );
+    assert!(user_state.record_purchase_time(1_060, 60).is_ok());
+    assert_eq!(user_state.last_purchase_at, 1_060);
+
+    // Without a cooldown purchases may follow each other immediately
+    assert!(user_state.record_purchase_time(1_060, 0).is_ok());
+}
+
+#[test]
+fn slot_limit_counts_purchases_per_slot() {
+    let mut ico_state = ICOState::default();
+    assert!(ico_state.record_slot_purchase(5, 2).is_ok());
+    assert!(ico_state.record_slot_purchase(5, 2).is_ok());
+    assert_eq!(
+        ico_state.record_slot_purchase(5, 2).unwrap_err(),
+        MyContractError::SlotPurchaseLimitReached.into()
+    );
+
+    // A new slot starts counting from one
+    assert!(ico_state.record_slot_purchase(6, 2).is_ok());
+    assert_eq!(
+        (ico_state.last_purchase_slot, ico_state.slot_purchases),
+        (6, 1)
+    );
+
+    // Zero leaves the slot unlimited
+    for _ in 0..10 {
+        assert!(ico_state.record_slot_purchase(7, 0).is_ok());
+    }
+    assert_eq!(ico_state.slot_purchases, 10);
+}
+
+// Ed25519 program instruction data checking one signature over `message`,
+// with every offset pointing into the instruction itself
+fn ed25519_data(public_key: &[u8; 32], message: &[u8]) -> Vec<u8> {
+    let public_key_at: u16 = 2 + 14;
+    let signature_at = public_key_at + 32;
+    let message_at = signature_at + 64;
+    let mut data = vec![1, 0];
+    for field in [
+        signature_at,
+        THIS_INSTRUCTION,
+        public_key_at,
+        THIS_INSTRUCTION,
+        message_at,
+        message.len() as u16,
+        THIS_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(&[0; 64]);
+    data.extend_from_slice(message);
+    data
+}
+
+#[test]
+fn parses_a_single_inline_signature() {
+    let verifier = Pubkey::new_unique().to_bytes();
+    let message = attestation::message(&Pubkey::new_unique(), &Pubkey::new_unique(), 1_000);
+    let data = ed25519_data(&verifier, &message);
+
+    let (signer, signed) = attestation::parse_ed25519(&data).unwrap();
+    assert_eq!(signer, verifier);
+    assert_eq!(signed, message);
+    assert_eq!(signed.len(), ATTESTATION_LEN);
+}
+
+#[test]
+fn rejects_other_ed25519_instructions() {
+    let verifier = Pubkey::new_unique().to_bytes();
+    let data = ed25519_data(&verifier, &[7; ATTESTATION_LEN]);
+
+    // Too short to hold an offsets record
+    assert!(attestation::parse_ed25519(&data[..15]).is_none());
+
+    // Anything but exactly one signature
+    for count in [0, 2] {
+        let mut other = data.clone();
+        other[0] = count;
+        assert!(attestation::parse_ed25519(&other).is_none());
+    }
+
+    // Signature, key or message read from another instruction
+    for field in [1, 3, 6] {
+        let mut other = data.clone();
+        let at = 2 + field * 2;
+        other[at..at + 2].copy_from_slice(&0u16.to_le_bytes());
+        assert!(attestation::parse_ed25519(&other).is_none());
+    }
+
+    // A message running past the end of the data
+    let mut other = data.clone();
+    other[12..14].copy_from_slice(&(ATTESTATION_LEN as u16 + 1).to_le_bytes());
+    assert!(attestation::parse_ed25519(&other).is_none());
+}
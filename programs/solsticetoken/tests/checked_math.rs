@@ -0,0 +1,194 @@
+use proptest::prelude::*;
+use solsticetoken::error::MyContractError;
+use solsticetoken::pricing;
+use solsticetoken::rewards;
+use solsticetoken::state::{ICOState, RoundingPolicy, SupplyAllocation, TokenPrice, UserState};
+
+fn rounding() -> impl Strategy<Value = RoundingPolicy> {
+    prop_oneof![
+        Just(RoundingPolicy::Down),
+        Just(RoundingPolicy::Nearest),
+        Just(RoundingPolicy::Up),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn quote_never_charges_more_than_paid(
+        tokens in any::<u64>(),
+        lamports in any::<u64>(),
+        amount in any::<u64>(),
+        rounding in rounding(),
+    ) {
+        let price = TokenPrice { tokens, lamports };
+        if let Ok(quote) = pricing::quote(&price, amount, rounding) {
+            prop_assert!(quote.tokens > 0);
+            prop_assert!(quote.cost <= amount);
+            // Every base unit handed out is paid for in full
+            prop_assert_eq!(pricing::lamports_for(&price, quote.tokens).unwrap(), quote.cost);
+        }
+    }
+
+    #[test]
+    fn quote_refuses_unpaid_rounding(
+        tokens in 1..=u64::MAX,
+        lamports in 1..=u64::MAX,
+        amount in any::<u64>(),
+        rounding in rounding(),
+    ) {
+        let price = TokenPrice { tokens, lamports };
+        if let Ok(rounded) = pricing::tokens_for(&price, amount, rounding) {
+            if rounded > 0 && pricing::lamports_for(&price, rounded).is_ok_and(|cost| cost > amount) {
+                prop_assert_eq!(
+                    pricing::quote(&price, amount, rounding).unwrap_err(),
+                    MyContractError::InsufficientPayment.into()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quote_rounding_down_is_fully_paid(
+        tokens in 1..=u64::MAX,
+        lamports in 1..=u64::MAX,
+        amount in any::<u64>(),
+    ) {
+        let price = TokenPrice { tokens, lamports };
+        if let Ok(quote) = pricing::quote(&price, amount, RoundingPolicy::Down) {
+            prop_assert_eq!(pricing::lamports_for(&price, quote.tokens).unwrap(), quote.cost);
+        }
+    }
+
+    #[test]
+    fn record_sale_never_wraps(
+        sold in any::<u64>(),
+        allocated in any::<u64>(),
+        tokens in any::<u64>(),
+    ) {
+        let mut ico_state = ICOState {
+            total_tokens_sold: sold,
+            total_tokens_allocated: allocated,
+            ..Default::default()
+        };
+        match sold.checked_add(tokens) {
+            Some(total) if total <= allocated => {
+                prop_assert!(ico_state.record_sale(tokens).is_ok());
+                prop_assert_eq!(ico_state.total_tokens_sold, total);
+            }
+            Some(_) => {
+                prop_assert_eq!(ico_state.record_sale(tokens).unwrap_err(), MyContractError::OverPurchase.into());
+                prop_assert_eq!(ico_state.total_tokens_sold, sold);
+            }
+            None => {
+                prop_assert_eq!(ico_state.record_sale(tokens).unwrap_err(), MyContractError::MathOverflow.into());
+                prop_assert_eq!(ico_state.total_tokens_sold, sold);
+            }
+        }
+    }
+
+    #[test]
+    fn record_purchase_never_wraps(purchased in any::<u64>(), tokens in any::<u64>()) {
+        let mut user_state = UserState {
+            amount_purchased: purchased,
+            ..Default::default()
+        };
+        let result = user_state.record_purchase(tokens);
+        match purchased.checked_add(tokens) {
+            Some(total) => prop_assert_eq!(user_state.amount_purchased, total),
+            None => prop_assert_eq!(result.unwrap_err(), MyContractError::MathOverflow.into()),
+        }
+    }
+
+    #[test]
+    fn supply_split_adds_up_exactly(
+        total_supply in any::<u64>(),
+        rewards_bps in 0..=10_000u16,
+        distribution_bps in 0..=10_000u16,
+        team_bps in 0..=10_000u16,
+        liquidity_bps in any::<u16>(),
+    ) {
+        let allocation = SupplyAllocation {
+            total_supply,
+            rewards_bps,
+            distribution_bps,
+            team_bps,
+            liquidity_bps,
+        };
+        if let Ok(amounts) = allocation.split() {
+            let minted = amounts.iter().map(|a| *a as u128).sum::<u128>();
+            prop_assert_eq!(minted, total_supply as u128);
+        }
+    }
+
+    #[test]
+    fn rewards_due_never_wraps(purchased in any::<u64>(), claimed in any::<u64>()) {
+        let result = rewards::rewards_due(purchased, claimed);
+        match purchased.checked_mul(rewards::REWARD_MULTIPLIER) {
+            Some(total) if total >= claimed => prop_assert_eq!(result.unwrap(), total - claimed),
+            Some(_) => prop_assert_eq!(result.unwrap_err(), MyContractError::MathUnderflow.into()),
+            None => prop_assert_eq!(result.unwrap_err(), MyContractError::MathOverflow.into()),
+        }
+    }
+
+    #[test]
+    fn accrued_rewards_never_wraps(amount in any::<u64>(), from in any::<i64>(), now in any::<i64>()) {
+        match rewards::accrued_rewards(amount, from, now) {
+            Ok(accrued) => {
+                let elapsed = (now as i128 - from as i128) as u128;
+                prop_assert_eq!(accrued as u128, amount as u128 * elapsed / rewards::SECONDS_PER_YEAR as u128);
+            }
+            Err(err) => prop_assert!(
+                err == MyContractError::MathUnderflow.into() || err == MyContractError::MathOverflow.into()
+            ),
+        }
+        if now < from {
+            prop_assert!(rewards::accrued_rewards(amount, from, now).is_err());
+        }
+    }
+
+    #[test]
+    fn pledge_tokens_never_wrap(amount_usd in any::<u64>(), level in any::<u8>()) {
+        match rewards::pledge_tokens_for(amount_usd, level) {
+            Ok(tokens) => prop_assert!(tokens as u128 <= amount_usd as u128 * 2),
+            Err(err) => {
+                prop_assert_eq!(err, MyContractError::MathOverflow.into());
+                prop_assert!((1..=5).contains(&level));
+            }
+        }
+    }
+
+    #[test]
+    fn vesting_end_never_wraps(start in any::<i64>()) {
+        match start.checked_add(rewards::VESTING_PERIOD) {
+            Some(end) => prop_assert_eq!(rewards::vesting_end(start).unwrap(), end),
+            None => prop_assert_eq!(rewards::vesting_end(start).unwrap_err(), MyContractError::MathOverflow.into()),
+        }
+    }
+
+    #[test]
+    fn transfer_balances_conserve_lamports(from in any::<u64>(), to in any::<u64>(), amount in any::<u64>()) {
+        match rewards::transfer_balances(from, to, amount) {
+            Ok((new_from, new_to)) => {
+                prop_assert_eq!(new_from as u128 + new_to as u128, from as u128 + to as u128);
+                prop_assert_eq!(new_from, from - amount);
+            }
+            Err(err) => prop_assert!(amount > from || to.checked_add(amount).is_none(), "{:?}", err),
+        }
+    }
+}
+
+#[test]
+fn rounding_up_never_gives_away_a_base_unit() {
+    let price = TokenPrice {
+        tokens: 1,
+        lamports: 1_000,
+    };
+    for (rounding, amount) in [(RoundingPolicy::Nearest, 600), (RoundingPolicy::Up, 1)] {
+        assert_eq!(
+            pricing::quote(&price, amount, rounding).unwrap_err(),
+            MyContractError::InsufficientPayment.into()
+        );
+    }
+    let quote = pricing::quote(&price, 1_000, RoundingPolicy::Up).unwrap();
+    assert_eq!((quote.tokens, quote.cost), (1, 1_000));
+}
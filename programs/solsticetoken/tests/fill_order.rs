@@ -0,0 +1,158 @@
+use anchor_lang::prelude::Pubkey;
+use solsticetoken::error::MyContractError;
+use solsticetoken::pricing::fill_order;
+use solsticetoken::state::{
+    BotProtection, ICOState, PhaseDetail, PhaseOverflow, PhaseTiming, PriceSchedule, SaleConfig,
+    TokenPrice,
+};
+
+const NOW: i64 = 1_500;
+
+// Phase `index` sells 100 base units at `lamports` each
+fn phase(index: u8, lamports: u64) -> PhaseDetail {
+    PhaseDetail {
+        phase: index,
+        timing: PhaseTiming::Relative { duration: 1_000 },
+        token_price: TokenPrice {
+            tokens: 1,
+            lamports,
+        },
+        token_allocation: 100,
+        tokens_sold: 0,
+        purchases: 0,
+        max_per_wallet: 0,
+        allowlist_root: None,
+        bot_protection: BotProtection::default(),
+        price_schedule: PriceSchedule::Fixed,
+    }
+}
+
+fn sale(phase_overflow: PhaseOverflow) -> ICOState {
+    ICOState {
+        start_timestamp: 1_000,
+        total_tokens_allocated: 300,
+        phase_details: vec![phase(0, 10), phase(1, 20), phase(2, 40)],
+        config: SaleConfig {
+            phase_overflow,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn sold(ico_state: &ICOState) -> Vec<u64> {
+    ico_state
+        .phase_details
+        .iter()
+        .map(|p| p.tokens_sold)
+        .collect()
+}
+
+#[test]
+fn reject_fails_orders_beyond_the_phase() {
+    let mut ico_state = sale(PhaseOverflow::Reject);
+    let fill = fill_order(&mut ico_state, 0, 500, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (50, 500));
+    assert_eq!(sold(&ico_state), [50, 0, 0]);
+
+    assert_eq!(
+        fill_order(&mut ico_state, 0, 510, None, NOW).unwrap_err(),
+        MyContractError::PhaseCapExceeded.into()
+    );
+    assert_eq!(sold(&ico_state), [50, 0, 0]);
+
+    let fill = fill_order(&mut ico_state, 0, 500, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (50, 500));
+    assert_eq!(sold(&ico_state), [100, 0, 0]);
+}
+
+#[test]
+fn partial_fill_sells_what_is_left_at_its_price() {
+    let mut ico_state = sale(PhaseOverflow::PartialFill);
+    ico_state.phase_details[0].tokens_sold = 80;
+
+    let fill = fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (20, 200));
+    assert_eq!(fill.phase_tokens[..3], [20, 0, 0]);
+    assert_eq!(sold(&ico_state), [100, 0, 0]);
+
+    // Nothing spills into the next phase, and a sold out phase sells nothing
+    assert_eq!(
+        fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap_err(),
+        MyContractError::PhaseCapExceeded.into()
+    );
+}
+
+#[test]
+fn partial_fill_respects_the_sale_allocation() {
+    let mut ico_state = sale(PhaseOverflow::PartialFill);
+    ico_state.total_tokens_sold = 290;
+
+    let fill = fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (10, 100));
+}
+
+#[test]
+fn spill_to_next_buys_the_rest_from_later_phases() {
+    let mut ico_state = sale(PhaseOverflow::SpillToNext);
+    ico_state.phase_details[0].tokens_sold = 80;
+
+    // 20 units at 10, then 40 at 20
+    let fill = fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (60, 1_000));
+    assert_eq!(fill.phase_tokens[..3], [20, 40, 0]);
+    assert_eq!(sold(&ico_state), [100, 40, 0]);
+
+    // The other 60 at 20, then 15 at 40
+    let fill = fill_order(&mut ico_state, 0, 1_800, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (75, 1_800));
+    assert_eq!(fill.phase_tokens[..3], [0, 60, 15]);
+    assert_eq!(sold(&ico_state), [100, 100, 15]);
+}
+
+#[test]
+fn spill_to_next_stops_at_the_sale_allocation() {
+    let mut ico_state = sale(PhaseOverflow::SpillToNext);
+    ico_state.total_tokens_sold = 250;
+    ico_state.phase_details[0].tokens_sold = 100;
+    ico_state.phase_details[1].tokens_sold = 100;
+    ico_state.phase_details[2].tokens_sold = 50;
+
+    let fill = fill_order(&mut ico_state, 0, 100_000, None, NOW).unwrap();
+    assert_eq!((fill.quote.tokens, fill.quote.cost), (50, 2_000));
+    assert_eq!(sold(&ico_state), [100, 100, 100]);
+
+    ico_state.total_tokens_sold = 300;
+    assert_eq!(
+        fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap_err(),
+        MyContractError::PhaseCapExceeded.into()
+    );
+}
+
+#[test]
+fn spill_to_next_stays_within_the_buyers_eligibility() {
+    let verifier = Some(Pubkey::new_unique());
+    let gated = [
+        |phase: &mut PhaseDetail| phase.allowlist_root = Some([7; 32]),
+        |phase: &mut PhaseDetail| phase.bot_protection.verifier = Some(Pubkey::new_unique()),
+    ];
+    for gate in gated {
+        let mut ico_state = sale(PhaseOverflow::SpillToNext);
+        ico_state.phase_details[0].tokens_sold = 80;
+        gate(&mut ico_state.phase_details[1]);
+
+        // Phase 1 may admit different buyers, so only phase 0 is sold
+        let fill = fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap();
+        assert_eq!((fill.quote.tokens, fill.quote.cost), (20, 200));
+        assert_eq!(sold(&ico_state), [100, 0, 0]);
+    }
+
+    // Phases behind the same verifier still spill into each other
+    let mut ico_state = sale(PhaseOverflow::SpillToNext);
+    ico_state.phase_details[0].tokens_sold = 80;
+    for phase in ico_state.phase_details.iter_mut() {
+        phase.bot_protection.verifier = verifier;
+    }
+    let fill = fill_order(&mut ico_state, 0, 1_000, None, NOW).unwrap();
+    assert_eq!(fill.phase_tokens[..3], [20, 40, 0]);
+}
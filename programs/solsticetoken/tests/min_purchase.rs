@@ -0,0 +1,84 @@
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{
+    BotProtection, ICOState, PhaseDetail, PhaseTiming, PriceSchedule, SaleConfig, TokenPrice,
+};
+
+// Two phases of 100 base units with a minimum purchase of 30
+fn sale(batch_auction: bool) -> ICOState {
+    let phase = |index| PhaseDetail {
+        phase: index,
+        timing: PhaseTiming::Relative { duration: 1_000 },
+        token_price: TokenPrice {
+            tokens: 1,
+            lamports: 10,
+        },
+        token_allocation: 100,
+        tokens_sold: 0,
+        purchases: 0,
+        max_per_wallet: 0,
+        allowlist_root: None,
+        bot_protection: BotProtection::default(),
+        price_schedule: PriceSchedule::Fixed,
+    };
+    ICOState {
+        total_tokens_allocated: 200,
+        phase_details: vec![phase(0), phase(1)],
+        config: SaleConfig {
+            min_purchase: 30,
+            batch_auction,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+// Records a purchase of `tokens` from phase 0 and checks it against the minimum
+fn buy(ico_state: &mut ICOState, tokens: u64) -> anchor_lang::Result<()> {
+    let phase_remaining = ico_state.phase_details[0].tokens_remaining();
+    ico_state.phase_details[0].tokens_sold += tokens;
+    ico_state.total_tokens_sold += tokens;
+    ico_state.check_min_purchase(tokens, phase_remaining)
+}
+
+#[test]
+fn purchases_below_the_minimum_are_rejected() {
+    let mut ico_state = sale(false);
+    assert_eq!(
+        buy(&mut ico_state, 29).unwrap_err(),
+        MyContractError::BelowMinimumPurchase.into()
+    );
+    assert!(buy(&mut sale(false), 30).is_ok());
+}
+
+#[test]
+fn the_remainder_of_a_phase_may_be_below_the_minimum() {
+    let mut ico_state = sale(false);
+    assert!(buy(&mut ico_state, 90).is_ok());
+    assert!(buy(&mut ico_state, 10).is_ok());
+
+    // Only all of the remainder is exempt
+    let mut ico_state = sale(false);
+    assert!(buy(&mut ico_state, 80).is_ok());
+    assert_eq!(
+        buy(&mut ico_state, 10).unwrap_err(),
+        MyContractError::BelowMinimumPurchase.into()
+    );
+}
+
+#[test]
+fn the_last_tokens_of_the_sale_may_be_below_the_minimum() {
+    let mut ico_state = sale(false);
+    ico_state.total_tokens_allocated = 95;
+    assert!(buy(&mut ico_state, 80).is_ok());
+    assert!(buy(&mut ico_state, 15).is_ok());
+}
+
+#[test]
+fn batch_auction_bids_get_no_remainder_exemption() {
+    let mut ico_state = sale(true);
+    ico_state.phase_details[0].tokens_sold = 90;
+    assert_eq!(
+        ico_state.check_min_purchase(10, 10).unwrap_err(),
+        MyContractError::BelowMinimumPurchase.into()
+    );
+}
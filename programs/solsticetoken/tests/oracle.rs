@@ -0,0 +1,140 @@
+use anchor_lang::prelude::*;
+use solsticetoken::error::MyContractError;
+use solsticetoken::oracle::{self, OraclePrice, PYTH_PRICE_MIN_LEN};
+use solsticetoken::pricing;
+use solsticetoken::state::{OracleConfig, PaymentPricing, RoundingPolicy, TokenPrice};
+
+const NOW: i64 = 1_700_000_000;
+
+// Pyth price account with an 8 decimal exponent
+fn price_account(price: i64, conf: u64, publish_time: i64, status: u32) -> Vec<u8> {
+    let mut data = vec![0u8; PYTH_PRICE_MIN_LEN];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+    data[96..104].copy_from_slice(&publish_time.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[216..224].copy_from_slice(&conf.to_le_bytes());
+    data[224..228].copy_from_slice(&status.to_le_bytes());
+    data
+}
+
+fn config(feed: Pubkey) -> OracleConfig {
+    OracleConfig {
+        feed,
+        max_age: 60,
+        max_confidence_bps: 100,
+    }
+}
+
+#[test]
+fn reads_fake_price_account() {
+    let data = price_account(15_025_000_000, 10_000_000, NOW - 5, 1);
+    assert_eq!(
+        oracle::read_price(&data).unwrap(),
+        OraclePrice {
+            price: 15_025_000_000,
+            conf: 10_000_000,
+            expo: -8,
+            publish_time: NOW - 5,
+        }
+    );
+}
+
+#[test]
+fn rejects_malformed_accounts() {
+    let mut data = price_account(15_025_000_000, 0, NOW, 1);
+    data[0] ^= 1;
+    assert_eq!(
+        oracle::read_price(&data).unwrap_err(),
+        MyContractError::InvalidOracleAccount.into()
+    );
+
+    let data = price_account(15_025_000_000, 0, NOW, 1);
+    assert_eq!(
+        oracle::read_price(&data[..200]).unwrap_err(),
+        MyContractError::InvalidOracleAccount.into()
+    );
+
+    let halted = price_account(15_025_000_000, 0, NOW, 2);
+    assert_eq!(
+        oracle::read_price(&halted).unwrap_err(),
+        MyContractError::OraclePriceUnavailable.into()
+    );
+}
+
+#[test]
+fn rejects_stale_and_uncertain_prices() {
+    let config = config(Pubkey::new_unique());
+
+    let stale = oracle::read_price(&price_account(15_025_000_000, 0, NOW - 61, 1)).unwrap();
+    assert_eq!(
+        stale.validate(NOW, &config).unwrap_err(),
+        MyContractError::OraclePriceStale.into()
+    );
+
+    // 1% of $150.25 is allowed, anything wider is not
+    let edge = oracle::read_price(&price_account(15_025_000_000, 150_250_000, NOW, 1)).unwrap();
+    assert!(edge.validate(NOW, &config).is_ok());
+    let wide = oracle::read_price(&price_account(15_025_000_000, 150_250_001, NOW, 1)).unwrap();
+    assert_eq!(
+        wide.validate(NOW, &config).unwrap_err(),
+        MyContractError::OracleConfidenceTooWide.into()
+    );
+}
+
+#[test]
+fn converts_sol_payments_to_usd_before_tier_pricing() {
+    let feed = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports = 0;
+    let mut data = price_account(15_025_000_000, 10_000_000, NOW - 5, 1);
+    let info = AccountInfo::new(
+        &feed,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+
+    let rate = oracle::sol_usd_rate(&info, &config(feed), NOW).unwrap();
+
+    // $0.10 per whole token with 6 decimals, paid with 1 SOL
+    let phase_price = TokenPrice {
+        tokens: 1_000_000,
+        lamports: 100_000,
+    };
+    let price = PaymentPricing::Converted(rate)
+        .price(0, &phase_price)
+        .unwrap();
+    let quote = pricing::quote(&price, 1_000_000_000, RoundingPolicy::Down).unwrap();
+    assert_eq!(quote.tokens, 1_502_500_000);
+    assert_eq!(quote.cost, 1_000_000_000);
+}
+
+#[test]
+fn rejects_unexpected_feed() {
+    let feed = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let mut lamports = 0;
+    let mut data = price_account(15_025_000_000, 0, NOW, 1);
+    let info = AccountInfo::new(
+        &feed,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+
+    assert_eq!(
+        oracle::sol_usd_rate(&info, &config(Pubkey::new_unique()), NOW).unwrap_err(),
+        MyContractError::OracleFeedMismatch.into()
+    );
+}
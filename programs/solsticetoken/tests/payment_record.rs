@@ -0,0 +1,91 @@
+use anchor_lang::prelude::*;
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{ICOState, PaymentRecord, SaleConfig, PAYMENT_SEED};
+
+fn record_address(ico_state: &Pubkey, beneficiary: &Pubkey, payer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            PAYMENT_SEED,
+            ico_state.as_ref(),
+            beneficiary.as_ref(),
+            payer.as_ref(),
+        ],
+        &solsticetoken::ID,
+    )
+    .0
+}
+
+// A record as `buy_tokens` and `buy_tokens_for` open it
+fn record(beneficiary: Pubkey, payer: Pubkey) -> PaymentRecord {
+    PaymentRecord {
+        beneficiary,
+        payer,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn every_payer_gets_its_own_record() {
+    let (ico_state, beneficiary) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (exchange, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let own = record_address(&ico_state, &beneficiary, &beneficiary);
+    let paid_by_exchange = record_address(&ico_state, &beneficiary, &exchange);
+    let paid_by_other = record_address(&ico_state, &beneficiary, &other);
+    assert_ne!(own, paid_by_exchange);
+    assert_ne!(paid_by_exchange, paid_by_other);
+}
+
+#[test]
+fn refund_of_a_purchase_for_someone_else_goes_to_the_payer() {
+    let (beneficiary, exchange) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut paid_by_exchange = record(beneficiary, exchange);
+    paid_by_exchange
+        .record_payment(None, 5_000, 500, false)
+        .unwrap();
+    paid_by_exchange
+        .record_payment(Some(1), 70, 7, false)
+        .unwrap();
+
+    // A dust purchase the beneficiary paid for itself stays apart
+    let mut own = record(beneficiary, beneficiary);
+    own.record_payment(None, 1, 1, false).unwrap();
+
+    // `claim_refund` pays `payer`: the exchange gets back everything it paid
+    assert_eq!(paid_by_exchange.payer, exchange);
+    assert_eq!(paid_by_exchange.take_refund(None).unwrap(), (507, 5_000, 0));
+    assert_eq!(paid_by_exchange.take_refund(Some(1)).unwrap(), (0, 0, 70));
+    assert_eq!(
+        paid_by_exchange.take_refund(Some(1)).unwrap_err(),
+        MyContractError::NothingToRefund.into()
+    );
+    assert_eq!(own.take_refund(None).unwrap(), (1, 1, 0));
+}
+
+#[test]
+fn batch_settlement_refunds_the_payer_what_didnt_clear() {
+    let ico_state = ICOState {
+        total_tokens_allocated: 100,
+        total_committed: 200,
+        config: SaleConfig {
+            batch_auction: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut paid_by_exchange = record(Pubkey::new_unique(), Pubkey::new_unique());
+    paid_by_exchange
+        .record_payment(None, 1_001, 100, true)
+        .unwrap();
+    assert_eq!(paid_by_exchange.tokens_minted, 0);
+
+    // Half the bid clears; the kept half is rounded up in favour of the sale
+    assert_eq!(paid_by_exchange.settle(&ico_state).unwrap(), (50, 500));
+    assert_eq!(
+        (paid_by_exchange.sol_paid, paid_by_exchange.tokens_minted),
+        (501, 50)
+    );
+    assert_eq!(
+        paid_by_exchange.settle(&ico_state).unwrap_err(),
+        MyContractError::AlreadySettled.into()
+    );
+}
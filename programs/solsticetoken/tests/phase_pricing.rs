@@ -0,0 +1,210 @@
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{
+    BotProtection, ConversionRate, PaymentPricing, PhaseDetail, PhaseTiming, PriceSchedule,
+    PricingCurve, SaleConfig, TokenPrice,
+};
+
+fn price(tokens: u64, lamports: u64) -> TokenPrice {
+    TokenPrice { tokens, lamports }
+}
+
+fn phase(index: u8, price_schedule: PriceSchedule) -> PhaseDetail {
+    PhaseDetail {
+        phase: index,
+        timing: PhaseTiming::Relative { duration: 1_000 },
+        token_price: price(1_000, 1_000),
+        token_allocation: 1_000_000,
+        tokens_sold: 0,
+        purchases: 0,
+        max_per_wallet: 0,
+        allowlist_root: None,
+        bot_protection: BotProtection::default(),
+        price_schedule,
+    }
+}
+
+#[test]
+fn converted_pricing_needs_both_units() {
+    let phases = [phase(0, PriceSchedule::Fixed)];
+    for (payment_units, quote_units) in [(0, 1), (1, 0), (0, 0)] {
+        let pricing = PaymentPricing::Converted(ConversionRate {
+            payment_units,
+            quote_units,
+        });
+        assert_eq!(
+            pricing
+                .validate(&phases, &SaleConfig::default())
+                .unwrap_err(),
+            MyContractError::InvalidPrice.into()
+        );
+    }
+    let pricing = PaymentPricing::Converted(ConversionRate {
+        payment_units: 1,
+        quote_units: 1,
+    });
+    assert!(pricing.validate(&phases, &SaleConfig::default()).is_ok());
+}
+
+#[test]
+fn per_phase_pricing_prices_every_phase() {
+    let phases = [
+        phase(0, PriceSchedule::Fixed),
+        phase(1, PriceSchedule::Fixed),
+    ];
+    let short = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1)],
+    };
+    let zero = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1), price(1, 0)],
+    };
+    let full = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1), price(1, 2)],
+    };
+    assert_eq!(
+        short.validate(&phases, &SaleConfig::default()).unwrap_err(),
+        MyContractError::InvalidPrice.into()
+    );
+    assert_eq!(
+        zero.validate(&phases, &SaleConfig::default()).unwrap_err(),
+        MyContractError::InvalidPrice.into()
+    );
+    assert!(full.validate(&phases, &SaleConfig::default()).is_ok());
+}
+
+#[test]
+fn per_phase_pricing_rejects_dutch_auctions() {
+    let dutch = PriceSchedule::DutchAuction {
+        floor: price(1_000, 500),
+        steps: 0,
+    };
+    let phases = [phase(0, PriceSchedule::Fixed), phase(1, dutch)];
+    let per_phase = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1), price(1, 1)],
+    };
+    let converted = PaymentPricing::Converted(ConversionRate {
+        payment_units: 1,
+        quote_units: 1,
+    });
+    assert_eq!(
+        per_phase
+            .validate(&phases, &SaleConfig::default())
+            .unwrap_err(),
+        MyContractError::InvalidPrice.into()
+    );
+    assert!(converted.validate(&phases, &SaleConfig::default()).is_ok());
+}
+
+#[test]
+fn per_phase_pricing_rejects_bonding_curves() {
+    let phases = [phase(0, PriceSchedule::Fixed)];
+    let config = SaleConfig {
+        pricing_curve: Some(PricingCurve::Linear {
+            start_price: 1_000,
+            slope: 1,
+        }),
+        ..Default::default()
+    };
+    let per_phase = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1)],
+    };
+    let converted = PaymentPricing::Converted(ConversionRate {
+        payment_units: 1,
+        quote_units: 1,
+    });
+    assert_eq!(
+        per_phase.validate(&phases, &config).unwrap_err(),
+        MyContractError::InvalidPrice.into()
+    );
+    assert!(converted.validate(&phases, &config).is_ok());
+}
+
+#[test]
+fn batch_auctions_take_no_payment_mints() {
+    let phases = [phase(0, PriceSchedule::Fixed)];
+    let config = SaleConfig {
+        batch_auction: true,
+        ..Default::default()
+    };
+    let per_phase = PaymentPricing::PerPhase {
+        prices: vec![price(1, 1)],
+    };
+    let converted = PaymentPricing::Converted(ConversionRate {
+        payment_units: 1,
+        quote_units: 1,
+    });
+    for pricing in [per_phase, converted] {
+        assert_eq!(
+            pricing.validate(&phases, &config).unwrap_err(),
+            MyContractError::BatchAuctionSolOnly.into()
+        );
+    }
+}
+
+fn lamports_at(schedule: PriceSchedule, now: i64) -> u64 {
+    phase(0, schedule)
+        .price_at(1_000, 2_000, now)
+        .unwrap()
+        .lamports
+}
+
+#[test]
+fn fixed_price_ignores_the_clock() {
+    for now in [0, 1_000, 1_500, 2_000, 5_000] {
+        assert_eq!(lamports_at(PriceSchedule::Fixed, now), 1_000);
+    }
+}
+
+#[test]
+fn linear_decay_is_clamped_to_the_window() {
+    let schedule = PriceSchedule::DutchAuction {
+        floor: price(1_000, 400),
+        steps: 0,
+    };
+    assert_eq!(lamports_at(schedule, 0), 1_000);
+    assert_eq!(lamports_at(schedule, 1_000), 1_000);
+    assert_eq!(lamports_at(schedule, 1_500), 700);
+    assert_eq!(lamports_at(schedule, 2_000), 400);
+    assert_eq!(lamports_at(schedule, 9_000), 400);
+}
+
+#[test]
+fn linear_decay_rounds_in_favour_of_the_sale() {
+    // Over a 300 lamport range 0.3 off still charges the start price and
+    // 99.9 off charges 99 less
+    let schedule = PriceSchedule::DutchAuction {
+        floor: price(1_000, 700),
+        steps: 0,
+    };
+    assert_eq!(lamports_at(schedule, 1_001), 1_000);
+    assert_eq!(lamports_at(schedule, 1_004), 999);
+    assert_eq!(lamports_at(schedule, 1_333), 901);
+}
+
+#[test]
+fn stepwise_decay_moves_once_per_step() {
+    // Three drops of 200 lamports each, a quarter of the window apart
+    let schedule = PriceSchedule::DutchAuction {
+        floor: price(1_000, 400),
+        steps: 3,
+    };
+    assert_eq!(lamports_at(schedule, 500), 1_000);
+    assert_eq!(lamports_at(schedule, 1_000), 1_000);
+    assert_eq!(lamports_at(schedule, 1_249), 1_000);
+    assert_eq!(lamports_at(schedule, 1_250), 800);
+    assert_eq!(lamports_at(schedule, 1_749), 600);
+    assert_eq!(lamports_at(schedule, 1_750), 400);
+    assert_eq!(lamports_at(schedule, 1_999), 400);
+    assert_eq!(lamports_at(schedule, 3_000), 400);
+}
+
+#[test]
+fn stepwise_decay_rounds_in_favour_of_the_sale() {
+    // A 100 lamport range over three steps drops 33, 66, then the full 100
+    let schedule = PriceSchedule::DutchAuction {
+        floor: price(1_000, 900),
+        steps: 3,
+    };
+    assert_eq!(lamports_at(schedule, 1_250), 967);
+    assert_eq!(lamports_at(schedule, 1_500), 934);
+    assert_eq!(lamports_at(schedule, 1_750), 900);
+}
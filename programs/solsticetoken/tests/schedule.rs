@@ -0,0 +1,259 @@
+use solsticetoken::error::MyContractError;
+use solsticetoken::state::{
+    BotProtection, ICOState, OracleConfig, PhaseDetail, PhaseTiming, PricePolicy, PriceSchedule,
+    PricingCurve, SaleConfig, SaleStatus, TokenPrice, MAX_PHASES,
+};
+
+const NOW: i64 = 1_000;
+const START: i64 = 2_000;
+
+fn phase(index: u8, timing: PhaseTiming, lamports: u64) -> PhaseDetail {
+    PhaseDetail {
+        phase: index,
+        timing,
+        token_price: TokenPrice {
+            tokens: 1_000,
+            lamports,
+        },
+        token_allocation: 100,
+        tokens_sold: 0,
+        purchases: 0,
+        max_per_wallet: 0,
+        allowlist_root: None,
+        bot_protection: BotProtection::default(),
+        price_schedule: PriceSchedule::Fixed,
+    }
+}
+
+fn relative(index: u8) -> PhaseDetail {
+    phase(index, PhaseTiming::Relative { duration: 100 }, 1_000)
+}
+
+fn sale(phase_details: Vec<PhaseDetail>) -> ICOState {
+    ICOState {
+        start_timestamp: START,
+        total_tokens_allocated: 1_000,
+        phase_details,
+        ..Default::default()
+    }
+}
+
+fn rejects(ico_state: &ICOState, error: MyContractError) {
+    assert_eq!(ico_state.validate_schedule(NOW).unwrap_err(), error.into());
+}
+
+#[test]
+fn accepts_a_valid_schedule() {
+    let ico_state = sale(vec![
+        relative(0),
+        phase(
+            1,
+            PhaseTiming::Absolute {
+                start: 2_200,
+                end: 2_300,
+            },
+            1_000,
+        ),
+        relative(2),
+    ]);
+    assert!(ico_state.validate_schedule(NOW).is_ok());
+}
+
+#[test]
+fn rejects_empty_and_oversized_schedules() {
+    rejects(&sale(vec![]), MyContractError::EmptyPhaseSchedule);
+    let phases = (0..=MAX_PHASES as u8).map(relative).collect();
+    rejects(&sale(phases), MyContractError::TooManyPhases);
+}
+
+#[test]
+fn rejects_a_start_in_the_past() {
+    let ico_state = ICOState {
+        start_timestamp: NOW,
+        ..sale(vec![relative(0)])
+    };
+    rejects(&ico_state, MyContractError::StartNotInFuture);
+}
+
+#[test]
+fn rejects_phases_out_of_order() {
+    rejects(
+        &sale(vec![relative(0), relative(2)]),
+        MyContractError::InvalidPhaseOrder,
+    );
+}
+
+#[test]
+fn rejects_empty_relative_phases() {
+    let ico_state = sale(vec![phase(0, PhaseTiming::Relative { duration: 0 }, 1_000)]);
+    rejects(&ico_state, MyContractError::InvalidPhaseDuration);
+}
+
+#[test]
+fn rejects_empty_or_overlapping_windows() {
+    let empty = sale(vec![phase(
+        0,
+        PhaseTiming::Absolute {
+            start: 2_100,
+            end: 2_100,
+        },
+        1_000,
+    )]);
+    let before_start = sale(vec![phase(
+        0,
+        PhaseTiming::Absolute {
+            start: 1_900,
+            end: 2_100,
+        },
+        1_000,
+    )]);
+    let overlapping = sale(vec![
+        relative(0),
+        phase(
+            1,
+            PhaseTiming::Absolute {
+                start: 2_050,
+                end: 2_300,
+            },
+            1_000,
+        ),
+    ]);
+    for ico_state in [empty, before_start, overlapping] {
+        rejects(&ico_state, MyContractError::InvalidPhaseWindow);
+    }
+}
+
+#[test]
+fn rejects_unusable_prices() {
+    let free = sale(vec![phase(0, PhaseTiming::Relative { duration: 100 }, 0)]);
+    rejects(&free, MyContractError::InvalidPrice);
+
+    let mut rising_floor = relative(0);
+    rising_floor.price_schedule = PriceSchedule::DutchAuction {
+        floor: TokenPrice {
+            tokens: 1_000,
+            lamports: 2_000,
+        },
+        steps: 0,
+    };
+    rejects(&sale(vec![rising_floor]), MyContractError::InvalidPrice);
+
+    let mut curve_auction = sale(vec![relative(0)]);
+    curve_auction.config = SaleConfig {
+        pricing_curve: Some(PricingCurve::Linear {
+            start_price: 1_000,
+            slope: 1,
+        }),
+        batch_auction: true,
+        ..Default::default()
+    };
+    rejects(&curve_auction, MyContractError::InvalidPrice);
+}
+
+#[test]
+fn rejects_allocations_that_dont_fit() {
+    let mut empty = relative(0);
+    empty.token_allocation = 0;
+    rejects(&sale(vec![empty]), MyContractError::InvalidPhaseAllocation);
+
+    let ico_state = ICOState {
+        total_tokens_allocated: 150,
+        ..sale(vec![relative(0), relative(1)])
+    };
+    rejects(&ico_state, MyContractError::InvalidPhaseAllocation);
+}
+
+#[test]
+fn price_policy_controls_price_drops() {
+    let phases = vec![
+        relative(0),
+        phase(1, PhaseTiming::Relative { duration: 100 }, 900),
+    ];
+    rejects(&sale(phases.clone()), MyContractError::PhasePriceDecreased);
+
+    let mut ico_state = sale(phases);
+    ico_state.config.price_policy = PricePolicy::Any;
+    assert!(ico_state.validate_schedule(NOW).is_ok());
+}
+
+#[test]
+fn rejects_a_soft_cap_above_the_sale_allocation() {
+    let mut ico_state = sale(vec![relative(0)]);
+    ico_state.config.soft_cap = 1_001;
+    rejects(&ico_state, MyContractError::InvalidSoftCap);
+
+    ico_state.config.soft_cap = 1_000;
+    assert!(ico_state.validate_schedule(NOW).is_ok());
+}
+
+#[test]
+fn rejects_oracles_without_a_positive_max_age() {
+    for max_age in [-1, 0] {
+        let mut ico_state = sale(vec![relative(0)]);
+        ico_state.config.oracle = Some(OracleConfig {
+            max_age,
+            ..Default::default()
+        });
+        rejects(&ico_state, MyContractError::InvalidOracleConfig);
+    }
+    let mut ico_state = sale(vec![relative(0)]);
+    ico_state.config.oracle = Some(OracleConfig {
+        max_age: 60,
+        ..Default::default()
+    });
+    assert!(ico_state.validate_schedule(NOW).is_ok());
+}
+
+#[test]
+fn status_follows_the_phase_windows() {
+    // [2000, 2100), gap, [2200, 2300), [2300, 2400)
+    let ico_state = sale(vec![
+        relative(0),
+        phase(
+            1,
+            PhaseTiming::Absolute {
+                start: 2_200,
+                end: 2_300,
+            },
+            1_000,
+        ),
+        relative(2),
+    ]);
+    let status = |now| ico_state.compute_status(now).unwrap();
+    assert_eq!(status(1_999), SaleStatus::Pending);
+    assert_eq!(status(2_000), SaleStatus::Active(0));
+    assert_eq!(status(2_099), SaleStatus::Active(0));
+    assert_eq!(status(2_100), SaleStatus::Pending);
+    assert_eq!(status(2_200), SaleStatus::Active(1));
+    assert_eq!(status(2_300), SaleStatus::Active(2));
+    assert_eq!(status(2_400), SaleStatus::Ended);
+}
+
+#[test]
+fn selling_out_ends_the_sale_early() {
+    let mut ico_state = sale(vec![relative(0), relative(1)]);
+    for phase in ico_state.phase_details.iter_mut() {
+        phase.tokens_sold = phase.token_allocation;
+    }
+    assert_eq!(
+        ico_state.compute_status(1_999).unwrap(),
+        SaleStatus::Pending
+    );
+    assert_eq!(ico_state.compute_status(2_050).unwrap(), SaleStatus::Ended);
+
+    let mut ico_state = sale(vec![relative(0), relative(1)]);
+    ico_state.total_tokens_sold = ico_state.total_tokens_allocated;
+    assert_eq!(ico_state.compute_status(2_050).unwrap(), SaleStatus::Ended);
+}
+
+#[test]
+fn finalized_and_cancelled_sales_stay_put() {
+    for status in [SaleStatus::Finalized, SaleStatus::Cancelled] {
+        let ico_state = ICOState {
+            status,
+            ..sale(vec![relative(0)])
+        };
+        assert_eq!(ico_state.compute_status(1_500).unwrap(), status);
+        assert_eq!(ico_state.compute_status(2_050).unwrap(), status);
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+use solsticetoken::merkle;
+
+/// One allowlisted wallet and the base units it may buy in the phase (0 = no individual cap).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub wallet: Pubkey,
+    pub cap: u64,
+}
+
+/// Parses `wallet[,cap]` lines. Blank lines, `#` comments and a `wallet` header are skipped.
+pub fn parse_csv(input: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("wallet") {
+            continue;
+        }
+
+        let mut fields = line.split(',').map(str::trim);
+        let wallet = fields.next().unwrap_or_default();
+        let wallet = Pubkey::from_str(wallet)
+            .map_err(|_| format!("line {}: invalid wallet {:?}", number + 1, wallet))?;
+        let cap = match fields.next() {
+            Some(cap) if !cap.is_empty() => cap
+                .parse()
+                .map_err(|_| format!("line {}: invalid cap {:?}", number + 1, cap))?,
+            _ => 0,
+        };
+        if !seen.insert(wallet) {
+            return Err(format!("line {}: duplicate wallet {}", number + 1, wallet));
+        }
+        entries.push(Entry { wallet, cap });
+    }
+    Ok(entries)
+}
+
+/// Merkle tree over allowlist entries, hashed the same way `buy_tokens` verifies them.
+pub struct AllowlistTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl AllowlistTree {
+    pub fn new(entries: &[Entry]) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Err("the allowlist is empty".to_string());
+        }
+
+        let mut levels = vec![entries
+            .iter()
+            .map(|entry| merkle::leaf(&entry.wallet, entry.cap))
+            .collect::<Vec<_>>()];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => merkle::hash_pair(a, b),
+                    // An odd node out is promoted to the next level unchanged
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(Self { levels })
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Sibling hashes from the leaf at `index` up to the root.
+    pub fn proof(&self, mut index: usize) -> Vec<[u8; 32]> {
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,34 @@
+use std::{env, fs, process};
+
+use allowlist::{parse_csv, to_hex, AllowlistTree};
+
+// Usage: allowlist <wallets.csv>
+//
+// Prints the Merkle root to store in `PhaseDetail::allowlist_root`, followed by
+// one `wallet,cap,proof` line per entry where the proof is `:`-separated hex.
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| {
+        eprintln!("usage: allowlist <wallets.csv>");
+        process::exit(2);
+    });
+
+    let result = fs::read_to_string(&path)
+        .map_err(|e| format!("{}: {}", path, e))
+        .and_then(|csv| parse_csv(&csv))
+        .and_then(|entries| AllowlistTree::new(&entries).map(|tree| (entries, tree)));
+    let (entries, tree) = result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    println!("root,{}", to_hex(&tree.root()));
+    for (index, entry) in entries.iter().enumerate() {
+        let proof = tree
+            .proof(index)
+            .iter()
+            .map(|node| to_hex(node))
+            .collect::<Vec<_>>()
+            .join(":");
+        println!("{},{},{}", entry.wallet, entry.cap, proof);
+    }
+}
@@ -0,0 +1,37 @@
+use allowlist::{parse_csv, AllowlistTree};
+use anchor_lang::prelude::Pubkey;
+use solsticetoken::merkle;
+
+#[test]
+fn every_entry_verifies_against_the_root() {
+    for size in 1..=9 {
+        let csv = (0..size)
+            .map(|i| format!("{},{}", Pubkey::new_unique(), i * 100))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let entries = parse_csv(&csv).unwrap();
+        let tree = AllowlistTree::new(&entries).unwrap();
+
+        for (index, entry) in entries.iter().enumerate() {
+            let proof = tree.proof(index);
+            let leaf = merkle::leaf(&entry.wallet, entry.cap);
+            assert!(merkle::verify(&proof, &tree.root(), leaf));
+
+            let wrong_cap = merkle::leaf(&entry.wallet, entry.cap + 1);
+            assert!(!merkle::verify(&proof, &tree.root(), wrong_cap));
+        }
+    }
+}
+
+#[test]
+fn parse_csv_rejects_duplicates_and_bad_rows() {
+    let wallet = Pubkey::new_unique();
+    let csv = format!("wallet,cap\n# private round\n{}\n\n", wallet);
+    let entries = parse_csv(&csv).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].cap, 0);
+
+    assert!(parse_csv(&format!("{0}\n{0},5", wallet)).is_err());
+    assert!(parse_csv("not-a-wallet").is_err());
+    assert!(parse_csv(&format!("{},lots", wallet)).is_err());
+}